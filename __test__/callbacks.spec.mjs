@@ -0,0 +1,36 @@
+import assert from 'node:assert/strict'
+import { createRequire } from 'node:module'
+import { test } from 'node:test'
+
+const { Point, pointReturnedBy, classifyReturn, mapToObject } = createRequire(import.meta.url)(
+  process.env.NAPI_BIND_TEST_ADDON,
+)
+
+test('a returned class instance is cloned into Rust, through a promise too', async () => {
+  assert.deepEqual(await pointReturnedBy(() => new Point(1, 2)), [1, 2])
+  assert.deepEqual(await pointReturnedBy(async () => new Point(3, 4)), [3, 4])
+})
+
+test('a plain object is not taken for a class instance', async () => {
+  await assert.rejects(pointReturnedBy(() => ({ x: 1, y: 2 })), /^Error: UNKNOWN_RETURN_VALUE\. /)
+})
+
+test('call_raw tells promises, values and unknown returns apart', async () => {
+  assert.equal(await classifyReturn(async () => 1), 'promise')
+  assert.equal(await classifyReturn(() => 1), 'value')
+  assert.equal(await classifyReturn(() => 'one'), 'unknown')
+  assert.equal(await classifyReturn(() => {}), 'unknown')
+})
+
+test('a Map converts entry by entry', () => {
+  assert.deepEqual(mapToObject(new Map([['a', 1], ['b', 2]])), { a: 1, b: 2 })
+})
+
+test('a Map entry that is no [key, value] pair fails with INVALID_MAP_ENTRY', () => {
+  const entries = new Map([['a', 1]]).entries()
+  const malformed = { entries: () => ({ next: () => {
+    const step = entries.next()
+    return step.done ? step : { done: false, value: [step.value[0]] }
+  } }) }
+  assert.throws(() => mapToObject(malformed), /^Error: INVALID_MAP_ENTRY\. /)
+})
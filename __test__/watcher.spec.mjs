@@ -0,0 +1,54 @@
+import assert from 'node:assert/strict'
+import { createRequire } from 'node:module'
+import { test } from 'node:test'
+
+const { BindingWatcher } = createRequire(import.meta.url)(process.env.NAPI_BIND_ADDON)
+
+test('every listener receives each event until removed', async () => {
+  const watcher = new BindingWatcher()
+  const received = []
+  const first = watcher.addListener((event) => received.push(['first', event.eventKind()]))
+  watcher.addListener((event) => received.push(['second', event.eventKind()]))
+  await watcher.restart()
+  assert.equal(watcher.removeListener(first), true)
+  assert.equal(watcher.removeListener(first), false)
+  assert.equal(watcher.removeListener(1234), false)
+  await watcher.restart()
+  await watcher.close()
+  assert.deepEqual(received, [
+    ['first', 'restart'],
+    ['second', 'restart'],
+    ['second', 'restart'],
+    ['second', 'close'],
+  ])
+})
+
+test('dry run calls no listener but still counts and queues events', async () => {
+  const watcher = new BindingWatcher({ paths: [], dryRun: true })
+  let calls = 0
+  watcher.addListener(() => calls++)
+  const polled = watcher.nextEvents(1)
+  await watcher.restart()
+  assert.deepEqual(
+    (await polled).map((event) => event.eventKind()),
+    ['restart'],
+  )
+  assert.equal(watcher.stats().totalEvents, 1)
+  await watcher.close()
+  assert.equal(calls, 0)
+})
+
+test('a shared handle drives the same watcher', async () => {
+  const watcher = new BindingWatcher()
+  const shared = watcher.share()
+  const received = []
+  watcher.addListener((event) => received.push(event.eventKind()))
+  await shared.restart()
+  assert.deepEqual(received, ['restart'])
+  assert.equal(shared.stats().totalEvents, 1)
+  assert.equal(watcher.stats().totalEvents, 1)
+  assert.equal(watcher.isClosed(), false)
+  await shared.close()
+  assert.equal(watcher.isClosed(), true)
+  assert.deepEqual(received, ['restart', 'close'])
+})
@@ -3,28 +3,309 @@
 export declare class BindingBundleEndEventData {
   output: string
   duration: number
+  /** Every file the bundle emitted, `output` included, e.g. to list the artifacts of a build. */
+  outputs(): Array<BindingOutputFile>
 }
 
 export declare class BindingError {
   kind: string
   message: string
+  /**
+   * How many times this error occurred. Always `1` unless the watcher de-duplicates errors,
+   * see `BindingWatcherOptions::dedupe_errors`.
+   */
+  count: number
+  /** The stack trace of the JS error this error was created from, if any. */
+  stack?: string
+  /**
+   * A stable code identifying the error: the `code` of the JS error it was created from, or the
+   * code our own error messages start with, e.g. `CALLBACK_TIMED_OUT`.
+   */
+  code?: string
 }
 
+/**
+ * Cloning gives another handle to the same watcher, as does `share` on the JS side: listeners,
+ * options, stats and the closed state are all shared between the handles.
+ */
 export declare class BindingWatcher {
-  constructor()
+  /** `options` are the watcher-wide options, see `reconfigure`. */
+  constructor(options?: BindingWatcherOptions)
+  /**
+   * Another handle to this watcher, e.g. to hand to code that shouldn't hold the original.
+   * Both drive the same watcher, so closing either closes it for both.
+   */
+  share(): BindingWatcher
+  /** A snapshot of the watcher-wide options, as last set by `reconfigure`. */
+  options(): BindingWatcherOptions
+  /**
+   * Replace the watcher-wide options. Takes effect for the events that follow. Fails with
+   * `INVALID_CONFIG` if an `ignore` glob doesn't parse or the `ordering` is unknown.
+   */
+  reconfigure(opts: BindingWatcherOptions): void
+  /**
+   * Attach `ctx` (e.g. a build id) to every event emitted from now on, including in the event
+   * log. Replaces any previous context.
+   */
+  setContext(ctx: string): void
+  /** Counters describing what the watcher did with the events it was given. */
+  stats(): BindingWatcherStats
+  /** Register a named watch config. Names must be unique within a watcher. */
+  addConfig(name: string, opts: BindingWatcherOptions): void
+  configNames(): Array<string>
+  /**
+   * Register another listener and return the id to remove it with. Listeners added while an
+   * event is being emitted only receive the events that follow.
+   */
+  addListener(listener: (data: BindingWatcherEvent) => void): number
+  /**
+   * Returns `false` if no listener is registered under `id`. A listener removed while an event
+   * is being emitted still receives that event.
+   */
+  removeListener(id: number): boolean
+  /**
+   * Skip the listener registered under `id` in every emit until `resumeListener`, while the
+   * other listeners carry on. The events emitted meanwhile are not delivered to it later.
+   * Returns `false` if no listener is registered under `id`. Batch listeners can't be paused.
+   */
+  pauseListener(id: number): boolean
+  /** Returns `false` if the listener registered under `id` wasn't paused. */
+  resumeListener(id: number): boolean
+  /**
+   * Like `addListener`, but the listener is only called for events whose `eventKind()` is one
+   * of `kinds`. Remove it with `removeListener`.
+   */
+  addListenerFor(kinds: Array<string>, listener: (data: BindingWatcherEvent) => void): number
+  /**
+   * Feed the recorded events to `listener` in order, for a late subscriber such as a devtools
+   * panel. Only the last `max_replay` events are replayed when given, and at most the latest
+   * `history_capacity` are on record. With `subscribe`, the listener is then registered for
+   * live events and its id is returned; events emitted during the replay itself are not
+   * delivered to it.
+   */
+  replayTo(listener: (data: BindingWatcherEvent) => void, maxReplay?: number, subscribe?: boolean): Promise<number | null>
+  /**
+   * The latest `limit` events on record, oldest first, or all of them without a `limit`. There
+   * are never more than `history_capacity`.
+   */
+  recentEvents(limit?: number): Array<BindingWatcherEvent>
+  /**
+   * Register a listener for the changes whose kind is in `mask`, e.g. `"create|delete"`. A
+   * `change_batch` is delivered whole when any of its changes matches. Remove it with
+   * `removeListener`.
+   */
+  addChangeListener(mask: string, listener: (data: BindingWatcherEvent) => void): number
+  /**
+   * Register a listener for the events no `addListenerFor` or `addChangeListener` listener
+   * matched, like the default case of a `switch`. Listeners added with `addListener` see every
+   * event and don't count as a match. Remove it with `removeListener`.
+   */
+  addFallbackListener(listener: (data: BindingWatcherEvent) => void): number
+  /**
+   * Register a listener called once per burst with all of its events, whatever their kinds;
+   * tell them apart with `eventKind()`. A single emitted event arrives as a one-element array.
+   * Batch listener ids are separate from `addListener` ids.
+   */
+  addBatchListener(listener: (events: Array<BindingWatcherEvent>) => void): number
+  removeBatchListener(id: number): boolean
+  /**
+   * Stop the watcher: deliver a final `close` event, then abandon the listener calls still
+   * pending and abort the watcher's background tasks. Nothing is emitted afterwards. Closing an
+   * already closed watcher does nothing.
+   *
+   * A listener may await `close` too: the emit that called it stops awaiting its listeners,
+   * and the teardown waits until that emit is done.
+   */
+  close(): Promise<void>
+  /**
+   * Resolve with the next `count` events emitted, in order, or with those emitted until the
+   * watcher is closed if fewer. Events are buffered from the first call to this or to `poll`
+   * on, and each is handed out to one of them only.
+   */
+  nextEvents(count: number): Promise<Array<BindingWatcherEvent>>
+  isClosed(): boolean
+  /**
+   * Reset the sequence counter and emit a `restart` event carrying the new base (`0`), so
+   * consumers tracking `seq` know the following gap-free numbering starts over.
+   */
+  restart(): Promise<void>
+  /**
+   * Like `start`, but drives the watcher on the calling task instead of a spawned one, which
+   * suits single-threaded runtimes and simple scripts. The returned promise only settles once
+   * the watcher is closed.
+   */
+  startBlocking(listener: (data: BindingWatcherEvent) => void): Promise<void>
+  /**
+   * Register `listener` and start watching the watcher-wide `paths` and those of every config
+   * added so far, recursively. File changes are emitted as `change` events (or batches, see
+   * `batchWindowMs`) and watching failures as bundle `ERROR` events, until the watcher is
+   * closed.
+   *
+   * Resolves once every path is watched, so changes made right after
+   * `await watcher.start(listener)` are not missed, and fails with `START_FAILED` if a path
+   * can't be watched.
+   *
+   * A panic while handling an event is reported as an `ERROR` event carrying an error of kind
+   * `panic`, and the watcher carries on with the next event.
+   */
   start(listener: (data: BindingWatcherEvent) => void): Promise<void>
+  /** Call `listener` once in the background, then wait until the watcher is closed. */
   loopSpawn(listener: (data: BindingWatcherEvent) => void): Promise<void>
 }
 
 export declare class BindingWatcherChangeData {
   path: string
-  kind: string
+  /** Where the file was before a `rename`. */
+  oldPath?: string
+  /** `create`, `update`, `delete` or `rename`. */
+  get kind(): string
 }
 
 export declare class BindingWatcherEvent {
   eventKind(): string
+  /**
+   * Whether both events are of the same kind with equal payloads. Where they were emitted
+   * (`config`, `seq`, `causeSeq`) is not compared.
+   */
+  equals(other: BindingWatcherEvent): boolean
+  /** Name of the watch config this event originates from, if any. */
+  config(): string | null
+  /** The app-level context the watcher carried when the event was emitted, see `setContext`. */
+  context(): string | null
+  /**
+   * Position of the event in the watcher's stream. It increases by one per emitted event and
+   * resets to `0` on `start` and `restart`; the `restart` event itself carries the new base.
+   */
+  seq(): number
+  /**
+   * For a bundle `END`, the `seq` of the latest change of the same config that triggered the
+   * rebuild. `None` when no such change was seen since the previous `END`.
+   */
+  causeSeq(): number | null
+  /** Throws for anything but a `change` event, so JS can catch the mismatch. */
   watchChangeData(): BindingWatcherChangeData
+  tryWatchChangeData(): BindingWatcherChangeData | null
+  watchChangeDataResult(): BindingWatcherChangeData
+  /** The changes carried by a `change` or `change_batch` event, empty for every other event. */
+  changes(): Array<BindingWatcherChangeData>
+  /** The output and duration of a finished build. Throws for anything but a bundle `END` event. */
   bundleEndData(): BindingBundleEndEventData
+  tryBundleEndData(): BindingBundleEndEventData | null
+  bundleEndDataResult(): BindingBundleEndEventData
+  /** The kind of a bundle event, e.g. `BUNDLE_END`. Throws for anything but an `event` event. */
   bundleEventKind(): string
+  tryBundleEventKind(): string | null
+  /** The stats carried by a `summary` event. Throws for every other event. */
+  summary(): BindingWatcherStats
+  /** The name a `custom` event was emitted under. Throws for every other event. */
+  customName(): string
+  /**
+   * The payload of a `custom` event, as the object its type serializes to. Throws for every
+   * other event.
+   */
+  customPayload(): any
+  /**
+   * The errors carried by a bundle `ERROR` event, empty for every other event. Reading them
+   * leaves the event untouched, so every call returns the same list.
+   */
   errors(): Array<Error | BindingError>
 }
+
+export interface BindingOutputFile {
+  path: string
+  /** In bytes. */
+  size: number
+}
+
+export interface BindingWatcherOptions {
+  paths: Array<string>
+  /**
+   * Globs of the paths whose changes are ignored while watching, e.g. to skip `node_modules`.
+   * They're matched against absolute paths, without `.` or `..` components.
+   */
+  ignore?: Array<string>
+  /** Whether to watch `paths` recursively. Defaults to `true`. */
+  recursive?: boolean
+  /** Collect the changes arriving within this many milliseconds into a single batch event. */
+  batchWindowMs?: number
+  /**
+   * Collapse the changes to the same path arriving within this many milliseconds of its first
+   * one into their net effect, e.g. a `create` then `update` into a `create`. A `create` then
+   * `delete` cancels out and emits nothing. A `delete` and a `create` of the same file (by
+   * inode) are reported as a single `rename`. Applies before `batch_window_ms`.
+   */
+  debounceMs?: number
+  /**
+   * Maximum number of events forwarded per second, by event kind (as returned by
+   * `eventKind()`). Excess events are dropped and counted in `stats().droppedEvents`. Kinds
+   * without an entry are unlimited.
+   */
+  rateLimits?: Record<string, number>
+  /**
+   * Report each distinct error (same kind and message) once per bundle cycle. Every `ERROR`
+   * event then carries all the distinct errors seen since the config's latest `BUNDLE_START`,
+   * each with the number of times it occurred in `count`. Defaults to `false`.
+   */
+  dedupeErrors?: boolean
+  /**
+   * Cancel the bundle running in `emitJoinSet` when a change to the same config arrives, and
+   * emit a `BUNDLE_START` for the fresh bundle the change calls for. Defaults to `false`.
+   */
+  cancelInFlightOnChange?: boolean
+  /**
+   * Emit a `summary` event carrying `stats()` right before the final `close` event. Defaults
+   * to `false`.
+   */
+  summaryOnClose?: boolean
+  /**
+   * Cap on the total time spent awaiting listeners for a single event (or burst, for batch
+   * listeners). Once it's used up, the listeners still to be awaited are abandoned and logged.
+   */
+  emitBudgetMs?: number
+  /**
+   * How the events waiting to be picked up through `poll` are ordered: `fifo` (the default)
+   * keeps emit order, `priority` hands out bundle `ERROR` and `END` events ahead of the other
+   * events already waiting. With `priority`, an error can thus be seen before changes emitted
+   * earlier. Listeners are called as each event is emitted, so their order never changes.
+   */
+  ordering?: string
+  /**
+   * Log each event at `info` level instead of calling the listeners, e.g. to check which
+   * events get produced regardless of what the listeners do. Everything else, such as `stats()`
+   * and `poll`, works as usual. Defaults to `false`.
+   */
+  dryRun?: boolean
+  /**
+   * Deliver an event again to a listener that threw or rejected on it, up to this many times,
+   * before its error is handled as usual. The next event is only delivered to the listener
+   * once it has handled this one, so it sees each event until it succeeds. Defaults to `0`.
+   */
+  maxRedeliveries?: number
+  /**
+   * How many of the latest events are kept on record for `replayTo` and `recentEvents`. Once
+   * that many are, each new event overwrites the oldest. Lowering it drops the oldest events
+   * with the next emit, and `0` keeps none. Defaults to `128`.
+   */
+  historyCapacity?: number
+}
+
+export interface BindingWatcherStats {
+  /** Events emitted, not counting those dropped by the rate limiter. */
+  totalEvents: number
+  /** Bundle `ERROR` events among `totalEvents`. */
+  errors: number
+  /** Milliseconds since the latest `start`, or since the watcher was created if never started. */
+  sessionDurationMs: number
+  /** Events dropped by the rate limiter. */
+  droppedEvents: number
+  /** Listener calls started and not settled yet. */
+  pendingCalls: number
+  /** The highest `pendingCalls` seen since the latest `start`, for capacity planning. */
+  peakQueueDepth: number
+  /**
+   * Average milliseconds a listener call waited between being queued and entering JS, over
+   * every watcher's calls so far. Only measured with the `metrics` feature, and unset before
+   * the first call.
+   */
+  avgFfiLatencyMs?: number
+}
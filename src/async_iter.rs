@@ -0,0 +1,133 @@
+//! Callbacks returning a JS async iterator, e.g. an `async function*`, consumed as a Rust
+//! `Stream`.
+
+use std::sync::Arc;
+
+use futures::{stream, Stream};
+use napi::{
+    bindgen_prelude::{
+        FromNapiValue, Function, JsObjectValue, JsValuesTupleIntoVec, Object, Promise, TypeName,
+        ValidateNapiValue,
+    },
+    sys,
+    threadsafe_function::ThreadsafeFunction,
+    Either, Status, ValueType,
+};
+
+use crate::js_call::{pretty_type_name, JsCallback, JsCallbackReturn};
+
+/// One `{ done, value }` result of an iterator's `next()`. `value` is only read when not `done`.
+struct IteratorStep<T>(Option<T>);
+
+impl<T: FromNapiValue> FromNapiValue for IteratorStep<T> {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> napi::Result<Self> {
+        let step = Object::from_raw(env, napi_val);
+        if step
+            .get_named_property::<Option<bool>>("done")?
+            .unwrap_or(false)
+        {
+            return Ok(Self(None));
+        }
+        step.get_named_property_unchecked("value")
+            .map(|value| Self(Some(value)))
+    }
+}
+
+type NextFn<T> = ThreadsafeFunction<(), Promise<IteratorStep<T>>, (), Status, false, true>;
+
+/// A JS async iterator, driven from Rust through its `next()` method.
+pub struct JsAsyncIterator<T: 'static + FromNapiValue> {
+    next: Arc<NextFn<T>>,
+}
+
+impl<T: 'static + FromNapiValue> JsAsyncIterator<T> {
+    /// The next value, or `None` once the iterator is done.
+    pub async fn next(&self) -> napi::Result<Option<T>> {
+        let step = self.next.call_async(()).await?.await?;
+        Ok(step.0)
+    }
+}
+
+impl<T: 'static + FromNapiValue> TypeName for JsAsyncIterator<T> {
+    fn type_name() -> &'static str {
+        "AsyncIterator"
+    }
+
+    fn value_type() -> ValueType {
+        ValueType::Object
+    }
+}
+
+impl<T: 'static + FromNapiValue> ValidateNapiValue for JsAsyncIterator<T> {}
+
+impl<T: 'static + FromNapiValue> FromNapiValue for JsAsyncIterator<T> {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> napi::Result<Self> {
+        let iter = Object::from_raw(env, napi_val);
+        let next: Function<'_, (), Promise<IteratorStep<T>>> = iter.get_named_property("next")?;
+        let next = next
+            .bind(iter)?
+            .build_threadsafe_function()
+            .weak::<true>()
+            .build()?;
+        Ok(Self {
+            next: Arc::new(next),
+        })
+    }
+}
+
+/// A callback returning an async iterator of `Ret`, typically an `async function*`.
+///
+/// - Rust: `AsyncGeneratorJsCallback<FnArgs<(String,)>, u32>`
+/// - Js: `(a: string) => AsyncIterator<number>`
+pub type AsyncGeneratorJsCallback<Args, Ret> = JsCallback<Args, JsAsyncIterator<Ret>>;
+
+pub trait AsyncGeneratorJsCallbackExt<Args, Ret> {
+    /// Call the Js function and yield every value its async iterator produces, as it produces
+    /// them, until the iterator is done. The stream ends after the first error.
+    fn await_call_stream(&self, args: Args) -> impl Stream<Item = napi::Result<Ret>> + Send;
+}
+
+enum StreamState<Args, Ret: 'static + FromNapiValue> {
+    Call(Args),
+    Next(JsAsyncIterator<Ret>),
+    Done,
+}
+
+impl<Args, Ret> AsyncGeneratorJsCallbackExt<Args, Ret> for AsyncGeneratorJsCallback<Args, Ret>
+where
+    Args: 'static + Send + JsValuesTupleIntoVec,
+    Ret: 'static + Send + JsCallbackReturn,
+{
+    fn await_call_stream(&self, args: Args) -> impl Stream<Item = napi::Result<Ret>> + Send {
+        let callback = Arc::clone(self);
+        stream::unfold(StreamState::Call(args), move |state| {
+            let callback = Arc::clone(&callback);
+            async move {
+                let iter = match state {
+                    StreamState::Call(args) => match callback.call_async(args).await {
+                        Ok(Either::A(iter)) => iter,
+                        Ok(Either::B(_unknown)) => {
+                            let e = napi::Error::new(
+                                Status::InvalidArg,
+                                format!(
+                                    "UNKNOWN_RETURN_VALUE. Expected an async iterator of `{}` in {}.",
+                                    pretty_type_name::<Ret>(),
+                                    pretty_type_name::<Self>(),
+                                ),
+                            );
+                            return Some((Err(e), StreamState::Done));
+                        }
+                        Err(e) => return Some((Err(e), StreamState::Done)),
+                    },
+                    StreamState::Next(iter) => iter,
+                    StreamState::Done => return None,
+                };
+                match iter.next().await {
+                    Ok(Some(value)) => Some((Ok(value), StreamState::Next(iter))),
+                    Ok(None) => None,
+                    Err(e) => Some((Err(e), StreamState::Done)),
+                }
+            }
+        })
+    }
+}
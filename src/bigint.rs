@@ -0,0 +1,134 @@
+//! Conversions for JS `BigInt`s returned from callbacks.
+//!
+//! NAPI-RS reads `i64` from a JS `number` and doesn't read the 64/128-bit integers from a `BigInt`
+//! at all, since that may lose precision. Use [`JsBigInt`] as `Ret` for callbacks returning a
+//! `BigInt`, e.g. `MaybeAsyncJsCallback<Args, JsBigInt<u64>>`, then read it with
+//! [`JsBigInt::get`]. A value that doesn't fit the target type fails with `BIGINT_OUT_OF_RANGE`
+//! instead of being truncated.
+
+use std::marker::PhantomData;
+
+use napi::{
+    bindgen_prelude::{BigInt, FromNapiValue, TypeName, ValidateNapiValue},
+    sys, ValueType,
+};
+
+use crate::js_call::pretty_type_name;
+
+/// Integer types a `BigInt` can be read into without loss.
+pub trait FromBigInt: Sized {
+    /// `None` if `value` is out of range for `Self`.
+    fn from_bigint(value: &BigInt) -> Option<Self>;
+}
+
+impl FromBigInt for u64 {
+    fn from_bigint(value: &BigInt) -> Option<Self> {
+        let (_, value, lossless) = value.get_u64();
+        lossless.then_some(value)
+    }
+}
+
+impl FromBigInt for i64 {
+    fn from_bigint(value: &BigInt) -> Option<Self> {
+        let (value, lossless) = value.get_i64();
+        lossless.then_some(value)
+    }
+}
+
+impl FromBigInt for u128 {
+    fn from_bigint(value: &BigInt) -> Option<Self> {
+        let (signed, value, lossless) = value.get_u128();
+        (lossless && (!signed || value == 0)).then_some(value)
+    }
+}
+
+impl FromBigInt for i128 {
+    fn from_bigint(value: &BigInt) -> Option<Self> {
+        let (value, lossless) = value.get_i128();
+        lossless.then_some(value)
+    }
+}
+
+/// A JS `BigInt` to be read into the Rust integer `T` with [`JsBigInt::get`].
+///
+/// The range check happens in `get` rather than while converting the return value. A callback's
+/// return value is converted as an `Either` of `Promise<Ret>`, `Ret` and `UnknownReturnValue`,
+/// which moves on to the next variant when one fails to convert, so an out-of-range `BigInt` would
+/// only come out as an `UNKNOWN_RETURN_VALUE` error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsBigInt<T> {
+    value: BigInt,
+    target: PhantomData<T>,
+}
+
+impl<T: FromBigInt> JsBigInt<T> {
+    /// The value as `T`, or `BIGINT_OUT_OF_RANGE` if it doesn't fit.
+    pub fn get(&self) -> napi::Result<T> {
+        T::from_bigint(&self.value).ok_or_else(|| {
+            napi::Error::new(
+                napi::Status::InvalidArg,
+                format!(
+                    "BIGINT_OUT_OF_RANGE. The BigInt doesn't fit in `{}`.",
+                    pretty_type_name::<T>()
+                ),
+            )
+        })
+    }
+
+    pub fn into_inner(self) -> BigInt {
+        self.value
+    }
+}
+
+impl<T> TypeName for JsBigInt<T> {
+    fn type_name() -> &'static str {
+        "BigInt"
+    }
+
+    fn value_type() -> ValueType {
+        ValueType::BigInt
+    }
+}
+
+impl<T> ValidateNapiValue for JsBigInt<T> {}
+
+impl<T> FromNapiValue for JsBigInt<T> {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> napi::Result<Self> {
+        Ok(Self {
+            value: BigInt::from_napi_value(env, napi_val)?,
+            target: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bigint(sign_bit: bool, words: &[u64]) -> JsBigInt<u64> {
+        JsBigInt {
+            value: BigInt {
+                sign_bit,
+                words: words.to_vec(),
+            },
+            target: PhantomData,
+        }
+    }
+
+    #[test]
+    fn bigint_in_range_reads_losslessly() {
+        assert_eq!(bigint(false, &[u64::MAX]).get().unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn bigint_out_of_range_fails_instead_of_truncating() {
+        for value in [bigint(false, &[0, 1]), bigint(true, &[1])] {
+            let e = value.get().unwrap_err();
+            assert!(
+                e.reason.starts_with("BIGINT_OUT_OF_RANGE. "),
+                "{}",
+                e.reason
+            );
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! The time source behind the time-based behavior (batch windows, rate limits, callback timeouts),
+//! so tests can drive it deterministically instead of waiting on the wall clock.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures::{channel::oneshot, FutureExt};
+
+use tokio_with_wasm::alias as tokio;
+
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Resolve once `duration` has passed according to this clock.
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+/// The default clock: `Instant::now` and tokio's timer. Under `tokio::time::pause` sleeps follow
+/// the paused runtime clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// A clock that only moves when told to. Sleeps resolve as `advance` carries the clock past their
+/// deadline.
+pub struct MockClock {
+    state: Mutex<MockState>,
+}
+
+struct MockState {
+    now: Instant,
+    sleepers: Vec<(Instant, oneshot::Sender<()>)>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(MockState {
+                now: Instant::now(),
+                sleepers: Vec::new(),
+            }),
+        }
+    }
+
+    /// Move the clock forward by `by`, waking every sleep whose deadline is now due.
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.now += by;
+        let now = state.now;
+        let (due, pending) = std::mem::take(&mut state.sleepers)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= now);
+        state.sleepers = pending;
+        drop(state);
+        for (_, wake) in due {
+            let _ = wake.send(());
+        }
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.state.lock().unwrap().now
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        let mut state = self.state.lock().unwrap();
+        if duration.is_zero() {
+            return Box::pin(std::future::ready(()));
+        }
+        let (wake, woken) = oneshot::channel();
+        let deadline = state.now + duration;
+        state.sleepers.push((deadline, wake));
+        Box::pin(woken.map(|_| ()))
+    }
+}
@@ -0,0 +1,83 @@
+//! Conversions for JS collections returned from callbacks.
+//!
+//! A JS `Set` already converts into `HashSet<T>` through NAPI-RS, so `Ret = HashSet<T>` works with
+//! `await_call` as is. `HashMap<K, V>` on the other hand is read from the own keys of a plain
+//! object, which a JS `Map` doesn't have. Use [`JsMap`] as `Ret` for callbacks returning a `Map`.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    ops::{Deref, DerefMut},
+};
+
+use napi::{
+    bindgen_prelude::{
+        Array, FromNapiValue, Function, JsObjectValue, Object, TypeName, ValidateNapiValue,
+    },
+    sys, ValueType,
+};
+
+/// A JS `Map` read into a Rust `HashMap` by walking its `entries()` iterator.
+#[derive(Clone, Debug, Default)]
+pub struct JsMap<K, V>(pub HashMap<K, V>);
+
+impl<K, V> JsMap<K, V> {
+    pub fn into_inner(self) -> HashMap<K, V> {
+        self.0
+    }
+}
+
+impl<K, V> Deref for JsMap<K, V> {
+    type Target = HashMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K, V> DerefMut for JsMap<K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<K, V> TypeName for JsMap<K, V> {
+    fn type_name() -> &'static str {
+        "Map"
+    }
+
+    fn value_type() -> ValueType {
+        ValueType::Object
+    }
+}
+
+impl<K: FromNapiValue, V: FromNapiValue> ValidateNapiValue for JsMap<K, V> {}
+
+impl<K, V> FromNapiValue for JsMap<K, V>
+where
+    K: FromNapiValue + Eq + Hash,
+    V: FromNapiValue,
+{
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> napi::Result<Self> {
+        let obj = Object::from_raw(env, napi_val);
+        let entries: Function<'_, (), Object> = obj.get_named_property("entries")?;
+        let iter = entries.apply(obj, ())?;
+        let next: Function<'_, (), Object> = iter.get_named_property("next")?;
+        let mut map = HashMap::new();
+        loop {
+            let step: Object = next.apply(iter, ())?;
+            if step.get_named_property::<bool>("done")? {
+                break;
+            }
+            let entry: Array = step.get_named_property("value")?;
+            let (Some(key), Some(value)) = (entry.get::<K>(0)?, entry.get::<V>(1)?) else {
+                return Err(napi::Error::new(
+                    napi::Status::InvalidArg,
+                    "INVALID_MAP_ENTRY. Expect Map entries to be [key, value] pairs.",
+                ));
+            };
+            map.insert(key, value);
+        }
+        Ok(Self(map))
+    }
+}
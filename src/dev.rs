@@ -0,0 +1,33 @@
+//! Helpers for the crate's own tests and examples. Enabled by the `dev` feature.
+
+use std::sync::Once;
+
+use tracing_subscriber::EnvFilter;
+
+static INIT_TRACING: Once = Once::new();
+
+/// Install a `fmt` subscriber that honors `RUST_LOG`, so `tracing` output from the crate becomes
+/// visible while running tests and examples. Calling it more than once is a no-op.
+///
+/// This is meant for development only. Production embedders should install their own subscriber
+/// instead of relying on this one.
+pub fn init_tracing() {
+    INIT_TRACING.call_once(|| {
+        // Another subscriber may already be installed by the embedding test harness; keep it.
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::from_default_env())
+            .try_init();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn init_tracing_twice_keeps_the_first_subscriber() {
+        init_tracing();
+        init_tracing();
+        tracing::info!("still logging after a second init");
+    }
+}
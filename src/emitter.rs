@@ -0,0 +1,162 @@
+//! A registry of JS listeners sharing the same callback signature.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+use napi::bindgen_prelude::JsValuesTupleIntoVec;
+
+use crate::js_call::{JsCallbackReturn, MaybeAsyncJsCallback, MaybeAsyncJsCallbackExt};
+
+/// Listeners are kept in registration order, which is also the order they are invoked in.
+pub struct Emitter<Args, Ret>
+where
+    Args: 'static + JsValuesTupleIntoVec,
+    Ret: 'static + JsCallbackReturn,
+{
+    listeners: Mutex<BTreeMap<u32, MaybeAsyncJsCallback<Args, Ret>>>,
+    next_id: AtomicU32,
+}
+
+impl<Args, Ret> Emitter<Args, Ret>
+where
+    Args: 'static + Send + JsValuesTupleIntoVec,
+    Ret: 'static + Send + JsCallbackReturn,
+{
+    pub fn new() -> Self {
+        Self {
+            listeners: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU32::new(0),
+        }
+    }
+
+    /// Register `listener` and return the id to remove it with.
+    pub fn add_listener(&self, listener: MaybeAsyncJsCallback<Args, Ret>) -> u32 {
+        let id = self.reserve_id();
+        self.listeners.lock().unwrap().insert(id, listener);
+        id
+    }
+
+    /// Take the next id without registering anything, for listeners kept outside this registry
+    /// that should share its id space and ordering.
+    pub fn reserve_id(&self) -> u32 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Returns `false` if no listener is registered under `id`.
+    pub fn remove_listener(&self, id: u32) -> bool {
+        self.listeners.lock().unwrap().remove(&id).is_some()
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.listeners.lock().unwrap().contains_key(&id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listeners.lock().unwrap().is_empty()
+    }
+
+    /// A snapshot of the currently registered listeners, in registration order. The registry isn't
+    /// locked while the snapshot is in use, so listeners may be added or removed meanwhile.
+    pub fn listeners(&self) -> Vec<(u32, MaybeAsyncJsCallback<Args, Ret>)> {
+        let listeners = self.listeners.lock().unwrap();
+        listeners
+            .iter()
+            .map(|(id, listener)| (*id, listener.clone()))
+            .collect()
+    }
+
+    /// Invoke every listener with `args`, awaiting each in turn, and collect their results in
+    /// registration order. A failing listener contributes its error in its place, or is left out
+    /// with `skip_failed`.
+    ///
+    /// This used to return `napi::Result<Vec<Ret>>` and stop at the first failure. Callers that
+    /// want that back can `.into_iter().collect::<napi::Result<Vec<_>>>()` the results.
+    pub async fn emit_and_collect(&self, args: Args, skip_failed: bool) -> Vec<napi::Result<Ret>>
+    where
+        Args: Clone,
+    {
+        let listeners = self.listeners();
+        collect(
+            listeners
+                .iter()
+                .map(|(_, listener)| listener.await_call(args.clone())),
+            skip_failed,
+        )
+        .await
+    }
+}
+
+/// Await `calls` one after the other, see [`Emitter::emit_and_collect`].
+async fn collect<Ret, F: Future<Output = napi::Result<Ret>>>(
+    calls: impl IntoIterator<Item = F>,
+    skip_failed: bool,
+) -> Vec<napi::Result<Ret>> {
+    let mut results = Vec::new();
+    for call in calls {
+        match call.await {
+            Err(_) if skip_failed => {}
+            result => results.push(result),
+        }
+    }
+    results
+}
+
+impl<Args, Ret> Default for Emitter<Args, Ret>
+where
+    Args: 'static + Send + JsValuesTupleIntoVec,
+    Ret: 'static + Send + JsCallbackReturn,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use futures::{executor::block_on, future};
+
+    use super::*;
+
+    fn call(started: &RefCell<Vec<u32>>, id: u32) -> future::Ready<napi::Result<u32>> {
+        started.borrow_mut().push(id);
+        future::ready(if id == 1 {
+            Err(napi::Error::from_reason("listener 1 failed"))
+        } else {
+            Ok(id)
+        })
+    }
+
+    fn reasons(results: Vec<napi::Result<u32>>) -> Vec<Result<u32, String>> {
+        results
+            .into_iter()
+            .map(|result| result.map_err(|e| e.reason))
+            .collect()
+    }
+
+    #[test]
+    fn collect_keeps_each_error_in_its_place() {
+        let started = RefCell::new(Vec::new());
+        let results = block_on(collect((0..3).map(|id| call(&started, id)), false));
+        assert_eq!(
+            reasons(results),
+            [Ok(0), Err("listener 1 failed".to_owned()), Ok(2)]
+        );
+        assert_eq!(*started.borrow(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn collect_with_skip_failed_leaves_errors_out_but_still_calls_every_listener() {
+        let started = RefCell::new(Vec::new());
+        let results = block_on(collect((0..3).map(|id| call(&started, id)), true));
+        assert_eq!(reasons(results), [Ok(0), Ok(2)]);
+        assert_eq!(*started.borrow(), [0, 1, 2]);
+    }
+}
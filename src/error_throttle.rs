@@ -0,0 +1,148 @@
+//! Collapses repeated identical error logs into a periodic count, so a listener that fails on
+//! every event doesn't flood the logs. The count of a burst still within its window when a watcher
+//! closes is logged then, see [`flush`].
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::clock::{Clock, TokioClock};
+
+/// How long identical errors are only counted after one was logged.
+const WINDOW: Duration = Duration::from_secs(10);
+
+static THROTTLE: LazyLock<Mutex<ErrorThrottle>> =
+    LazyLock::new(|| Mutex::new(ErrorThrottle::new(WINDOW, Arc::new(TokioClock))));
+
+/// What `diag_err!` should log for `message` right now, if anything, see [`ErrorThrottle::check`].
+pub(crate) fn throttled(message: String) -> Option<String> {
+    THROTTLE.lock().unwrap().check(message)
+}
+
+/// Log the repeats counted and not reported yet, as `diag_err!` would once their window is over.
+pub(crate) fn flush() {
+    let summaries = THROTTLE.lock().unwrap().flush();
+    if !crate::is_quiet() {
+        for summary in summaries {
+            tracing::error!("{summary}");
+        }
+    }
+}
+
+struct Repeats {
+    /// When the message was last logged.
+    logged_at: Instant,
+    /// Occurrences since then.
+    count: u32,
+}
+
+struct ErrorThrottle {
+    window: Duration,
+    seen: HashMap<String, Repeats>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ErrorThrottle {
+    fn new(window: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            window,
+            seen: HashMap::new(),
+            clock,
+        }
+    }
+
+    /// The message is returned as is the first time it's seen. Repeats within `window` of that are
+    /// only counted, and the first repeat after it comes back as a summary of the count.
+    fn check(&mut self, message: String) -> Option<String> {
+        let now = self.clock.now();
+        if let Some(repeats) = self.seen.get_mut(&message) {
+            repeats.count += 1;
+            if now.duration_since(repeats.logged_at) < self.window {
+                return None;
+            }
+            let count = std::mem::take(&mut repeats.count);
+            repeats.logged_at = now;
+            return Some(summary(message, count));
+        }
+        // Forget the messages that have gone quiet, so the map stays small.
+        let window = self.window;
+        self.seen.retain(|_, repeats| {
+            repeats.count > 0 || now.duration_since(repeats.logged_at) < window
+        });
+        self.seen.insert(
+            message.clone(),
+            Repeats {
+                logged_at: now,
+                count: 0,
+            },
+        );
+        Some(message)
+    }
+
+    /// Summaries of every message repeated since it was last logged, which then start over as if
+    /// just logged.
+    fn flush(&mut self) -> Vec<String> {
+        let now = self.clock.now();
+        self.seen
+            .iter_mut()
+            .filter(|(_, repeats)| repeats.count > 0)
+            .map(|(message, repeats)| {
+                let count = std::mem::take(&mut repeats.count);
+                repeats.logged_at = now;
+                summary(message.clone(), count)
+            })
+            .collect()
+    }
+}
+
+fn summary(message: String, count: u32) -> String {
+    if count == 1 {
+        message
+    } else {
+        format!("{message} (same error occurred {count} times)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn throttle() -> (ErrorThrottle, Arc<MockClock>) {
+        let clock = Arc::new(MockClock::new());
+        (ErrorThrottle::new(WINDOW, clock.clone()), clock)
+    }
+
+    #[test]
+    fn repeats_within_the_window_are_counted_into_one_summary() {
+        let (mut throttle, clock) = throttle();
+        assert_eq!(throttle.check("boom".to_string()).as_deref(), Some("boom"));
+        for _ in 0..3 {
+            assert_eq!(throttle.check("boom".to_string()), None);
+        }
+        assert_eq!(
+            throttle.check("other".to_string()).as_deref(),
+            Some("other")
+        );
+
+        clock.advance(WINDOW);
+        assert_eq!(
+            throttle.check("boom".to_string()).as_deref(),
+            Some("boom (same error occurred 4 times)")
+        );
+    }
+
+    #[test]
+    fn flush_reports_the_count_of_the_final_burst() {
+        let (mut throttle, _clock) = throttle();
+        throttle.check("boom".to_string());
+        throttle.check("boom".to_string());
+        throttle.check("boom".to_string());
+        throttle.check("quiet".to_string());
+
+        assert_eq!(throttle.flush(), ["boom (same error occurred 2 times)"]);
+        assert_eq!(throttle.flush(), Vec::<String>::new());
+    }
+}
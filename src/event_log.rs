@@ -0,0 +1,265 @@
+//! Recording of emitted `WatcherEvent`s, as NDJSON unless configured otherwise.
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::WatcherEvent;
+
+/// Number of events written between two flushes of the underlying writer.
+const DEFAULT_FLUSH_EVERY: usize = 16;
+
+/// One line of the event log.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventRecord {
+    pub config: Option<String>,
+    pub seq: u32,
+    /// App-level context set through `BindingWatcher::set_context` when the event was emitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    pub event: WatcherEvent,
+}
+
+/// How an [`EventRecord`] is encoded on the wire.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SerializationFormat {
+    /// One JSON object per line (NDJSON).
+    #[default]
+    Json,
+    /// Back-to-back MessagePack values, which are self-delimiting. Requires the `msgpack` feature.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl SerializationFormat {
+    /// Encode `record` as one self-contained frame, including the trailing newline for JSON.
+    pub fn encode(self, record: &EventRecord) -> io::Result<Vec<u8>> {
+        match self {
+            SerializationFormat::Json => {
+                let mut frame = serde_json::to_vec(record)?;
+                frame.push(b'\n');
+                Ok(frame)
+            }
+            #[cfg(feature = "msgpack")]
+            SerializationFormat::MessagePack => {
+                rmp_serde::to_vec_named(record).map_err(io::Error::other)
+            }
+        }
+    }
+
+    /// Decode a single frame produced by [`SerializationFormat::encode`].
+    pub fn decode(self, frame: &[u8]) -> io::Result<EventRecord> {
+        match self {
+            SerializationFormat::Json => Ok(serde_json::from_slice(frame)?),
+            #[cfg(feature = "msgpack")]
+            SerializationFormat::MessagePack => {
+                rmp_serde::from_slice(frame).map_err(io::Error::other)
+            }
+        }
+    }
+}
+
+/// Writes each event as one frame in the log's [`SerializationFormat`], one JSON object per line
+/// by default.
+///
+/// With the `gzip` feature, [`EventLog::gzip`] wraps the writer in a gzip encoder so archived
+/// logs of long-running sessions stay compact. The stream is flushed every few events, so a log
+/// that's cut short still decompresses up to the last flush. Dropping the log finishes the gzip
+/// stream.
+pub struct EventLog {
+    writer: Box<dyn Write + Send>,
+    format: SerializationFormat,
+    flush_every: usize,
+    unflushed: usize,
+}
+
+impl EventLog {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Box::new(writer),
+            format: SerializationFormat::default(),
+            flush_every: DEFAULT_FLUSH_EVERY,
+            unflushed: 0,
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    pub fn gzip(writer: impl Write + Send + 'static) -> Self {
+        use flate2::{write::GzEncoder, Compression};
+
+        Self::new(GzEncoder::new(writer, Compression::default()))
+    }
+
+    pub fn with_format(mut self, format: SerializationFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_flush_every(mut self, events: usize) -> Self {
+        self.flush_every = events.max(1);
+        self
+    }
+
+    pub fn write_event(
+        &mut self,
+        config: Option<&str>,
+        seq: u32,
+        event: &WatcherEvent,
+    ) -> io::Result<()> {
+        self.write_record(&EventRecord {
+            config: config.map(str::to_string),
+            seq,
+            context: None,
+            event: event.clone(),
+        })
+    }
+
+    pub fn write_record(&mut self, record: &EventRecord) -> io::Result<()> {
+        self.writer.write_all(&self.format.encode(record)?)?;
+        self.unflushed += 1;
+        if self.unflushed >= self.flush_every {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.unflushed = 0;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::BufRead,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::{BundleEvent, WatcherChangeData, WatcherChangeKind};
+
+    /// A writer whose bytes stay readable after it was moved into an `EventLog`.
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuf {
+        fn bytes(&self) -> Vec<u8> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn events() -> Vec<WatcherEvent> {
+        vec![
+            WatcherEvent::Event(BundleEvent::Start),
+            WatcherEvent::ReStart,
+            WatcherEvent::Close,
+        ]
+    }
+
+    fn write_events(log: &mut EventLog) {
+        for (seq, event) in events().iter().enumerate() {
+            log.write_event(Some("a"), seq as u32, event).unwrap();
+        }
+    }
+
+    /// `(seq, event)` of every record in the NDJSON read from `reader`.
+    fn read_records(reader: impl io::Read) -> Vec<(u32, WatcherEvent)> {
+        io::BufReader::new(reader)
+            .lines()
+            .map(|line| serde_json::from_str::<EventRecord>(&line.unwrap()).unwrap())
+            .map(|record| (record.seq, record.event))
+            .collect()
+    }
+
+    fn numbered(events: Vec<WatcherEvent>) -> Vec<(u32, WatcherEvent)> {
+        (0..).zip(events).collect()
+    }
+
+    #[test]
+    fn log_is_one_record_per_line() {
+        let buf = SharedBuf::default();
+        let mut log = EventLog::new(buf.clone());
+        write_events(&mut log);
+        log.flush().unwrap();
+
+        assert_eq!(read_records(buf.bytes().as_slice()), numbered(events()));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_log_decompresses_to_one_record_per_line() {
+        let buf = SharedBuf::default();
+        let mut log = EventLog::gzip(buf.clone());
+        write_events(&mut log);
+        drop(log);
+
+        let bytes = buf.bytes();
+        let decoder = flate2::read::GzDecoder::new(bytes.as_slice());
+        assert_eq!(read_records(decoder), numbered(events()));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_log_cut_short_decompresses_up_to_the_last_flush() {
+        let buf = SharedBuf::default();
+        let mut log = EventLog::gzip(buf.clone()).with_flush_every(1);
+        log.write_event(None, 0, &WatcherEvent::Close).unwrap();
+
+        let bytes = buf.bytes();
+        let mut line = String::new();
+        io::BufReader::new(flate2::read::GzDecoder::new(bytes.as_slice()))
+            .read_line(&mut line)
+            .unwrap();
+        let record: EventRecord = serde_json::from_str(&line).unwrap();
+        assert_eq!(record.event, WatcherEvent::Close);
+    }
+
+    fn record() -> EventRecord {
+        EventRecord {
+            config: Some("a".to_string()),
+            seq: 7,
+            context: Some("ctx".to_string()),
+            event: WatcherEvent::Change(WatcherChangeData {
+                path: "src/index.ts".to_string(),
+                kind: WatcherChangeKind::Update,
+                old_path: None,
+            }),
+        }
+    }
+
+    fn round_trip(format: SerializationFormat) {
+        let record = record();
+        let decoded = format.decode(&format.encode(&record).unwrap()).unwrap();
+        assert_eq!(
+            (decoded.config, decoded.seq, decoded.context, decoded.event),
+            (record.config, record.seq, record.context, record.event)
+        );
+    }
+
+    #[test]
+    fn json_frame_decodes_to_the_encoded_record() {
+        round_trip(SerializationFormat::Json);
+        assert!(SerializationFormat::Json
+            .encode(&record())
+            .unwrap()
+            .ends_with(b"}\n"));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn message_pack_frame_decodes_to_the_encoded_record() {
+        round_trip(SerializationFormat::MessagePack);
+    }
+}
@@ -0,0 +1,93 @@
+//! How long listener calls wait between being queued on their threadsafe function and entering
+//! JS, for `stats().avgFfiLatencyMs`. Only compiled with the `metrics` feature.
+//!
+//! A threadsafe function runs its calls in the order they were queued, so each listener keeps the
+//! times its calls were queued at in a FIFO, and the call entering JS takes the oldest one. Only
+//! the listeners converted through [`timed_listener`] are measured; calls to other threadsafe
+//! functions are passed over by [`queued`].
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, LazyLock, Mutex, OnceLock,
+    },
+    time::Instant,
+};
+
+use napi::{
+    bindgen_prelude::{FromNapiValue, Function, JsValuesTupleIntoVec},
+    sys,
+};
+
+use crate::js_call::MaybeAsyncJsCallback;
+
+type Queued = Arc<Mutex<VecDeque<Instant>>>;
+
+/// The FIFO of each measured listener, by its raw threadsafe function.
+static LISTENERS: LazyLock<Mutex<HashMap<usize, Queued>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static TOTAL_NANOS: AtomicU64 = AtomicU64::new(0);
+static SAMPLES: AtomicU64 = AtomicU64::new(0);
+
+/// Unregisters a listener's FIFO once its threadsafe function is finalized, which drops the
+/// callback owning this.
+struct Registration(Arc<OnceLock<usize>>);
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(raw) = self.0.get() {
+            LISTENERS.lock().unwrap().remove(raw);
+        }
+    }
+}
+
+/// `value` converted like `MaybeAsyncJsCallback::from_napi_value`, except that the calls to it are
+/// measured.
+pub(crate) unsafe fn timed_listener<Args: 'static + JsValuesTupleIntoVec>(
+    env: sys::napi_env,
+    value: sys::napi_value,
+) -> napi::Result<MaybeAsyncJsCallback<Args, ()>> {
+    let queued = Queued::default();
+    let raw = Arc::new(OnceLock::new());
+    let registration = Registration(Arc::clone(&raw));
+    let entered = Arc::clone(&queued);
+    let tsfn = Function::<Args, _>::from_napi_value(env, value)?
+        .build_threadsafe_function::<Args>()
+        .callee_handled::<false>()
+        .weak::<true>()
+        .build_callback(move |ctx| {
+            // Owned by the callback, to be dropped along with the threadsafe function.
+            let _registration = &registration;
+            if let Some(queued_at) = entered.lock().unwrap().pop_front() {
+                let nanos = queued_at
+                    .elapsed()
+                    .as_nanos()
+                    .try_into()
+                    .unwrap_or(u64::MAX);
+                TOTAL_NANOS.fetch_add(nanos, Ordering::Relaxed);
+                SAMPLES.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(ctx.value)
+        })?;
+    let key = tsfn.raw() as usize;
+    let _ = raw.set(key);
+    LISTENERS.lock().unwrap().insert(key, queued);
+    Ok(Arc::new(tsfn))
+}
+
+/// Note that a call to the threadsafe function `raw` is about to be queued. A call that then fails
+/// to queue, which only happens once the function is closing, leaves its time behind unused.
+pub(crate) fn queued(raw: sys::napi_threadsafe_function) {
+    let listeners = LISTENERS.lock().unwrap();
+    if let Some(queued) = listeners.get(&(raw as usize)) {
+        queued.lock().unwrap().push_back(Instant::now());
+    }
+}
+
+/// Average of every call measured so far, `None` before the first one.
+pub(crate) fn average_ms() -> Option<f64> {
+    let samples = SAMPLES.load(Ordering::Relaxed);
+    (samples > 0).then(|| TOTAL_NANOS.load(Ordering::Relaxed) as f64 / samples as f64 / 1e6)
+}
@@ -0,0 +1,292 @@
+//! Filesystem watching behind `BindingWatcher::start`, backed by `notify`.
+
+use std::{
+    io,
+    path::{self, Component, Path, PathBuf},
+};
+
+use futures::channel::mpsc::{self, UnboundedReceiver};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::{
+    event::{CreateKind, ModifyKind, RenameMode},
+    EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+
+use crate::WatcherChangeKind;
+
+/// What `notify` reported, tagged with the name of the watch config whose paths it concerns, or
+/// `None` for the watcher-wide paths.
+pub(crate) type FsEvent = (Option<String>, notify::Result<notify::Event>);
+
+/// The paths of one watch config, or of the watcher itself, and how to watch them.
+#[derive(Clone)]
+pub(crate) struct WatchSource {
+    pub config: Option<String>,
+    pub paths: Vec<String>,
+    pub recursive: bool,
+    /// Globs of the paths whose changes are dropped.
+    pub ignore: Vec<String>,
+}
+
+/// Watch the paths of every source and forward what `notify` reports to the returned receiver,
+/// minus the changes to ignored paths. Watching stops once the returned watchers are dropped.
+pub(crate) fn watch_all(
+    sources: Vec<WatchSource>,
+) -> Result<(Vec<RecommendedWatcher>, UnboundedReceiver<FsEvent>), String> {
+    let (tx, rx) = mpsc::unbounded();
+    let mut watchers = Vec::new();
+    for source in sources {
+        if source.paths.is_empty() {
+            continue;
+        }
+        let ignore = compile_globs(&source.ignore)?;
+        let config = source.config;
+        let tx = tx.clone();
+        // The tracker of the last `From` half of a rename, see `pair_rename`.
+        let mut rename_from = None;
+        let mut watcher =
+            notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+                let result = match result {
+                    Ok(event) => match pair_rename(event, &mut rename_from, &ignore) {
+                        Some(event) => Ok(event),
+                        None => return,
+                    },
+                    Err(e) => Err(e),
+                };
+                // The receiver is only gone once the watcher is closed.
+                let _ = tx.unbounded_send((config.clone(), result));
+            })
+            .map_err(|e| e.to_string())?;
+        let mode = if source.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        for path in &source.paths {
+            // `notify` reports paths under the one it watches, so that makes them normalized too.
+            let path = normalize(Path::new(path)).map_err(|e| e.to_string())?;
+            watcher.watch(&path, mode).map_err(|e| e.to_string())?;
+        }
+        watchers.push(watcher);
+    }
+    Ok((watchers, rx))
+}
+
+/// `event` minus its ignored paths, `None` if nothing's left to report.
+///
+/// Backends that can pair the halves of a rename (inotify) report the `From` half, then the `To`
+/// half, then both paths in a `Both` event. The `To` half is dropped since the `Both` event
+/// reports it too. The `From` half is held by `BindingWatcher::emit_fs_event` until the `Both`
+/// event arrives, or reported as a delete if it doesn't, as the file moved out of the watched
+/// paths.
+fn pair_rename(
+    mut event: notify::Event,
+    rename_from: &mut Option<usize>,
+    ignore: &GlobSet,
+) -> Option<notify::Event> {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => *rename_from = event.tracker(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::To))
+            if event.tracker().is_some() && event.tracker() == *rename_from =>
+        {
+            return None;
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+            let [from, to] = [&event.paths[0], &event.paths[1]].map(|path| ignore.is_match(path));
+            match (from, to) {
+                (false, false) => return Some(event),
+                // The file is gone as far as the watcher cares, which the held `From` half reports.
+                (_, true) => return None,
+                // The file moved in from an ignored path, which is a create.
+                (true, false) => {
+                    event.kind = EventKind::Create(CreateKind::Any);
+                    event.paths.remove(0);
+                    return Some(event);
+                }
+            }
+        }
+        _ => {}
+    }
+    event.paths.retain(|path| !ignore.is_match(path));
+    (!event.paths.is_empty()).then_some(event)
+}
+
+/// The inode of the file at `path`, to tell whether a file deleted and one created are the same
+/// file moved, on backends that report renames that way. `None` where there are no inodes.
+pub(crate) fn file_id(path: impl AsRef<Path>) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        std::fs::symlink_metadata(path)
+            .ok()
+            .map(|metadata| metadata.ino())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// The inode of every file under the paths of `source`, minus the ignored ones, see `file_id`.
+/// Subdirectories are only looked into when `source` is watched recursively, and symlinks aren't
+/// followed. Blocks on the filesystem for as long as the walk takes, so keep it off the runtime.
+pub(crate) fn file_ids(source: &WatchSource) -> Vec<(String, u64)> {
+    if cfg!(not(unix)) {
+        return Vec::new();
+    }
+    let Ok(ignore) = compile_globs(&source.ignore) else {
+        return Vec::new();
+    };
+    let mut ids = Vec::new();
+    let mut dirs = Vec::new();
+    for path in &source.paths {
+        let Ok(path) = normalize(Path::new(path)) else {
+            continue;
+        };
+        match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => dirs.push(path),
+            Ok(_) => ids.extend(file_id(&path).map(|id| (path.to_string_lossy().into_owned(), id))),
+            Err(_) => {}
+        }
+    }
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if ignore.is_match(&path) {
+                continue;
+            }
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => {
+                    if source.recursive {
+                        dirs.push(path);
+                    }
+                }
+                Ok(_) => {
+                    ids.extend(file_id(&path).map(|id| (path.to_string_lossy().into_owned(), id)))
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    ids
+}
+
+pub(crate) fn compile_globs(globs: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|e| e.to_string())?);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// `path` made absolute, with `.` and `..` resolved lexically, without following symlinks.
+pub(crate) fn normalize(path: &Path) -> io::Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path::absolute(path)?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    Ok(normalized)
+}
+
+/// The kind of change a `notify` event stands for. Events that don't change anything, e.g. a file
+/// being read, are handed back as the error.
+impl TryFrom<EventKind> for WatcherChangeKind {
+    type Error = EventKind;
+
+    fn try_from(kind: EventKind) -> Result<Self, Self::Error> {
+        match kind {
+            EventKind::Create(_) => Ok(WatcherChangeKind::Create),
+            // Both paths, the old one first.
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => Ok(WatcherChangeKind::Rename),
+            // The halves of a rename reported separately.
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => Ok(WatcherChangeKind::Delete),
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => Ok(WatcherChangeKind::Create),
+            // A path of a rename, but not which; it's reported as updated.
+            EventKind::Modify(ModifyKind::Name(_)) => Ok(WatcherChangeKind::Update),
+            EventKind::Modify(_) => Ok(WatcherChangeKind::Update),
+            EventKind::Remove(_) => Ok(WatcherChangeKind::Delete),
+            EventKind::Access(_) | EventKind::Any | EventKind::Other => Err(kind),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::event::{AccessKind, DataChange, MetadataKind, RemoveKind};
+
+    use super::*;
+
+    #[test]
+    fn notify_kinds_map_to_change_kinds() {
+        use WatcherChangeKind::*;
+        let cases = [
+            (EventKind::Create(CreateKind::File), Some(Create)),
+            (EventKind::Create(CreateKind::Folder), Some(Create)),
+            (
+                EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+                Some(Update),
+            ),
+            (
+                EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)),
+                Some(Update),
+            ),
+            (EventKind::Modify(ModifyKind::Any), Some(Update)),
+            (
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+                Some(Rename),
+            ),
+            (
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+                Some(Delete),
+            ),
+            (
+                EventKind::Modify(ModifyKind::Name(RenameMode::To)),
+                Some(Create),
+            ),
+            (
+                EventKind::Modify(ModifyKind::Name(RenameMode::Any)),
+                Some(Update),
+            ),
+            (EventKind::Remove(RemoveKind::File), Some(Delete)),
+            (EventKind::Access(AccessKind::Read), None),
+            (EventKind::Any, None),
+            (EventKind::Other, None),
+        ];
+        for (kind, expected) in cases {
+            // A rejected kind comes back as the error.
+            assert_eq!(WatcherChangeKind::try_from(kind), expected.ok_or(kind));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_ids_leave_out_ignored_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = normalize(dir.path()).unwrap();
+        std::fs::create_dir(dir.join("node_modules")).unwrap();
+        for file in ["a.txt", "node_modules/b.txt"] {
+            std::fs::write(dir.join(file), "x").unwrap();
+        }
+        let source = WatchSource {
+            config: None,
+            paths: vec![dir.to_string_lossy().into_owned()],
+            recursive: true,
+            ignore: vec!["**/node_modules".to_string()],
+        };
+        let paths: Vec<_> = file_ids(&source)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(paths, [dir.join("a.txt").to_string_lossy()]);
+    }
+}
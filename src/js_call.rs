@@ -1,29 +1,364 @@
-use std::sync::Arc;
+use std::{
+    any::Any,
+    collections::HashMap,
+    hash::Hash,
+    ops::Deref,
+    pin::{pin, Pin},
+    sync::{Arc, Mutex, RwLock},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
 
-use futures::Future;
+use futures::{
+    future::{self, join_all, select},
+    Future,
+};
 use napi::{
-    bindgen_prelude::{FromNapiValue, JsValuesTupleIntoVec, Promise},
-    threadsafe_function::{ThreadsafeFunction, UnknownReturnValue},
-    Either,
+    bindgen_prelude::{
+        ClassInstance, FnArgs, FromNapiValue, Function, JsValuesTupleIntoVec, MaybeTypeTag, Object,
+        Promise, ToNapiValue, TypeName, Unknown, ValidateNapiValue,
+    },
+    sys,
+    threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
+    Either, Env, Status, ValueType,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    clock::{Clock, TokioClock},
+    BindingError,
 };
-use tracing::{debug, error};
 
 use std::borrow::Cow;
 
 use regex::Regex;
-use std::sync::LazyLock;
+use std::sync::{LazyLock, OnceLock};
 
 static MODULE_MATCHER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?:\w+::)").unwrap());
 
+/// `std::any::type_name::<T>()` without module paths, e.g. `Either<String, i32>` for
+/// `napi::Either<alloc::string::String, i32>`. Every path is shortened, including those nested in
+/// generic arguments, tuples, references and slices, which keep their shape:
+/// `FnArgs<(Option<String>, i32)>`, `&[PathBuf]`.
+///
+/// Each name is only shortened once and then served from [`PRETTY_TYPE_NAMES`], so later calls
+/// don't allocate.
 pub fn pretty_type_name<T: ?Sized>() -> Cow<'static, str> {
     let type_name = std::any::type_name::<T>();
-    prettify_type_name(type_name)
+    let mut cache = PRETTY_TYPE_NAMES.lock().unwrap();
+    let pretty = cache.entry(type_name).or_insert_with(|| {
+        match prettify_type_name(type_name) {
+            Cow::Borrowed(pretty) => pretty,
+            // Leaked once per type, which there is a fixed number of.
+            Cow::Owned(pretty) => Box::leak(pretty.into_boxed_str()),
+        }
+    });
+    Cow::Borrowed(*pretty)
 }
 
-fn prettify_type_name(name: &str) -> Cow<str> {
+/// [`pretty_type_name`] results by `type_name`.
+static PRETTY_TYPE_NAMES: LazyLock<Mutex<HashMap<&'static str, &'static str>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn prettify_type_name(name: &str) -> Cow<'_, str> {
     MODULE_MATCHER_RE.replace_all(name, "")
 }
 
+/// A callback whose `Ret` is `()` discards whatever JS returns, so any non-error return counts as
+/// success. Returns `Some(())` as `Ret` in that case, `None` for every other `Ret`.
+fn discarded_return<Ret: 'static>() -> Option<Ret> {
+    let unit: Box<dyn Any> = Box::new(());
+    unit.downcast::<Ret>().ok().map(|ret| *ret)
+}
+
+/// The Node environments that loaded the addon, see [`track_env_teardown`].
+struct LiveEnvs {
+    count: usize,
+    /// Cancelled once the last of them is torn down, e.g. by `process.exit()`. Replaced when an
+    /// environment loads the addon after that, e.g. a new worker once the previous one exited.
+    teardown: CancellationToken,
+}
+
+static LIVE_ENVS: LazyLock<Mutex<LiveEnvs>> = LazyLock::new(|| {
+    Mutex::new(LiveEnvs {
+        count: 0,
+        teardown: CancellationToken::new(),
+    })
+});
+
+/// Register a cleanup hook on `env` so that, once the last environment using the addon is torn
+/// down, every pending call into JS fails with `RUNTIME_EXITING` instead of waiting forever on a
+/// promise that will never settle. Called once per environment when the module is loaded.
+pub fn track_env_teardown(env: &Env) -> napi::Result<()> {
+    env_loaded();
+    env.add_env_cleanup_hook((), |()| env_torn_down())?;
+    Ok(())
+}
+
+fn env_loaded() {
+    let mut envs = LIVE_ENVS.lock().unwrap();
+    if envs.count == 0 && envs.teardown.is_cancelled() {
+        envs.teardown = CancellationToken::new();
+    }
+    envs.count += 1;
+}
+
+fn env_torn_down() {
+    let mut envs = LIVE_ENVS.lock().unwrap();
+    envs.count -= 1;
+    if envs.count == 0 {
+        envs.teardown.cancel();
+    }
+}
+
+/// Await the call `start` makes unless `token` is cancelled first, failing with `CANCELLED` for
+/// the callback of type `C`. With `token` already cancelled, `start` isn't called at all, so there's
+/// no call future to drop unpolled.
+async fn until_cancelled<T, C: ?Sized, F: Future<Output = napi::Result<T>>>(
+    token: &CancellationToken,
+    start: impl FnOnce() -> F,
+) -> napi::Result<T> {
+    let cancelled = || {
+        napi::Error::new(
+            Status::Cancelled,
+            format!(
+                "CANCELLED. The call to {} was cancelled.",
+                pretty_type_name::<C>()
+            ),
+        )
+    };
+    if token.is_cancelled() {
+        return Err(cancelled());
+    }
+    token
+        .run_until_cancelled(start())
+        .await
+        .unwrap_or_else(|| Err(cancelled()))
+}
+
+/// Await the calls `attempt` makes until one succeeds or `attempts` of them failed, sleeping
+/// `backoff * n` on `clock` after the `n`th failure, see `await_call_retry`.
+async fn retry<T, F: Future<Output = napi::Result<T>>>(
+    attempts: u32,
+    backoff: Duration,
+    clock: &dyn Clock,
+    mut attempt: impl FnMut() -> F,
+) -> napi::Result<T> {
+    let mut failures = 1;
+    loop {
+        match attempt().await {
+            Err(_) if failures < attempts => {
+                clock.sleep(backoff * failures).await;
+                failures += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Await `call` unless the environment is torn down first.
+async fn until_env_teardown<T>(call: impl Future<Output = napi::Result<T>>) -> napi::Result<T> {
+    let teardown = LIVE_ENVS.lock().unwrap().teardown.clone();
+    teardown.run_until_cancelled(call).await.unwrap_or_else(|| {
+        Err(napi::Error::new(
+            Status::Cancelled,
+            "RUNTIME_EXITING. The Node environment was torn down while awaiting a JS call.",
+        ))
+    })
+}
+
+/// A no-op function of the first environment that loaded the addon, called to get onto its JS
+/// thread from Rust, see [`js_error_details`].
+static JS_THREAD: OnceLock<ThreadsafeFunction<(), (), (), Status, false, true>> = OnceLock::new();
+
+/// Keep a way onto the JS thread of `env` for [`js_error_details`], unless an earlier environment
+/// already provided one. Called once per environment when the module is loaded.
+pub fn track_js_thread(env: &Env) -> napi::Result<()> {
+    if JS_THREAD.get().is_some() {
+        return Ok(());
+    }
+    let noop = env.create_function_from_closure::<(), (), _>("jsThread", |_| Ok(()))?;
+    let tsfn = noop
+        .build_threadsafe_function::<()>()
+        .callee_handled::<false>()
+        .weak::<true>()
+        .build()?;
+    let _ = JS_THREAD.set(tsfn);
+    Ok(())
+}
+
+/// What the JS error behind a [`napi::Error`] carries besides its message.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct JsErrorDetails {
+    pub stack: Option<String>,
+    pub code: Option<String>,
+}
+
+/// Read the `stack` and `code` of the JS value `error` was created from, e.g. the rejection of a
+/// promise returned by a callback. `None` for an error built on the Rust side, and for one thrown
+/// in another environment than the first to load the addon, as its values can't be read from
+/// there.
+pub async fn js_error_details(error: &napi::Error) -> Option<JsErrorDetails> {
+    let js_thread = JS_THREAD.get()?;
+    let error = error.try_clone().ok()?;
+    let (tx, rx) = futures::channel::oneshot::channel();
+    let status = js_thread.call_with_return_value(
+        (),
+        ThreadsafeFunctionCallMode::NonBlocking,
+        move |_, env| {
+            let _ = tx.send(read_error_details(&env, error));
+            Ok(())
+        },
+    );
+    if status != Status::Ok {
+        return None;
+    }
+    until_env_teardown(async { Ok(rx.await.ok().flatten()) })
+        .await
+        .ok()
+        .flatten()
+}
+
+/// The JS thread side of [`js_error_details`].
+fn read_error_details(env: &Env, error: napi::Error) -> Option<JsErrorDetails> {
+    let status = error.status.as_ref().to_owned();
+    // Hands back the original JS value when it's readable here, or else a fresh error whose `code`
+    // is the status, with nothing more to tell.
+    let value = unsafe { ToNapiValue::to_napi_value(env.raw(), error) }.ok()?;
+    let Ok(object) = (unsafe { Object::from_napi_value(env.raw(), value) }) else {
+        // e.g. a rejection with a string, which has no stack.
+        return None;
+    };
+    let property = |name: &str| match object.get::<Either<String, Unknown>>(name) {
+        Ok(Some(Either::A(value))) => Some(value),
+        _ => None,
+    };
+    let code = property("code");
+    if code.as_deref() == Some(status.as_str()) {
+        return None;
+    }
+    Some(JsErrorDetails {
+        stack: property("stack"),
+        code,
+    })
+}
+
+/// `FromNapiValue` under a name that can carry a readable compile error. Bounds on a callback's return type use this
+/// trait, so a `Ret` that can't be converted from a JS value points the user here instead of deep into NAPI-RS's
+/// conversion machinery. Every `FromNapiValue` type that NAPI-RS can also try as one variant of an `Either` implements
+/// it, so a `Ret: JsCallbackReturn` bound alone implies `Either<Either<Promise<Ret>, Ret>, UnknownReturnValue>:
+/// FromNapiValue`.
+#[diagnostic::on_unimplemented(
+    message = "your callback return type `{Self}` must implement `FromNapiValue`",
+    label = "`{Self}` can't be converted from a JS value",
+    note = "it also needs `TypeName` and `ValidateNapiValue`, see the `JsCallback` docs for the supported return types"
+)]
+pub trait JsCallbackReturn: FromNapiValue + TypeName + ValidateNapiValue {}
+
+impl<T: FromNapiValue + TypeName + ValidateNapiValue> JsCallbackReturn for T {}
+
+/// Stands in for a value returned from JS that converts to none of the types a callback expects.
+/// Like napi's `UnknownReturnValue`, except that it keeps the type of the value, so the error
+/// can say what was returned instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownReturnValue(pub ValueType);
+
+impl UnknownReturnValue {
+    /// The type of the returned value as `typeof` would name it, e.g. `object`.
+    pub fn js_type(&self) -> String {
+        self.0.to_string().to_lowercase()
+    }
+}
+
+impl TypeName for UnknownReturnValue {
+    fn type_name() -> &'static str {
+        "UnknownReturnValue"
+    }
+
+    fn value_type() -> ValueType {
+        ValueType::Unknown
+    }
+}
+
+impl ValidateNapiValue for UnknownReturnValue {
+    unsafe fn validate(
+        _env: sys::napi_env,
+        _napi_val: sys::napi_value,
+    ) -> napi::Result<sys::napi_value> {
+        // Whatever the other variants of the `Either` reject ends up here.
+        Ok(std::ptr::null_mut())
+    }
+}
+
+impl FromNapiValue for UnknownReturnValue {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> napi::Result<Self> {
+        Ok(Self(Unknown::from_napi_value(env, napi_val)?.get_type()?))
+    }
+}
+
+/// What an unknown value returned by a callback of type `C` comes out as: the discarded `()` if
+/// that's the `Ret`, see [`discarded_return`], an `UNKNOWN_RETURN_VALUE` error otherwise.
+fn unknown_return<Ret: 'static, C: ?Sized>(
+    unknown: UnknownReturnValue,
+) -> Result<Ret, napi::Error> {
+    discarded_return::<Ret>().ok_or_else(|| {
+        napi::Error::new(
+            napi::Status::InvalidArg,
+            format!(
+                "UNKNOWN_RETURN_VALUE. Cannot convert {} to `{}` in {}.",
+                unknown.js_type(),
+                pretty_type_name::<Ret>(),
+                pretty_type_name::<C>(),
+            ),
+        )
+    })
+}
+
+/// A call future only reaches JS once it is polled, so one that is built and then dropped without
+/// being awaited silently never calls the listener. In debug builds the call futures returned by
+/// this module are wrapped in `WarnOnUnawaited`, which logs a warning when that happens.
+///
+/// A future that was polled at least once and then dropped, e.g. because it was cancelled or
+/// raced against a timeout, did reach JS and is deliberately not reported.
+#[cfg(debug_assertions)]
+pub struct WarnOnUnawaited<F> {
+    call: Pin<Box<F>>,
+    polled: bool,
+}
+
+#[cfg(debug_assertions)]
+impl<F: Future> Future for WarnOnUnawaited<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        self.polled = true;
+        self.call.as_mut().poll(cx)
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<F> Drop for WarnOnUnawaited<F> {
+    fn drop(&mut self) {
+        if !self.polled {
+            tracing::warn!("callback future dropped without being awaited");
+        }
+    }
+}
+
+/// Wrap `call` in [`WarnOnUnawaited`] in debug builds, return it unchanged otherwise.
+#[cfg(debug_assertions)]
+pub fn warn_on_unawaited<F: Future>(call: F) -> WarnOnUnawaited<F> {
+    WarnOnUnawaited {
+        call: Box::pin(call),
+        polled: false,
+    }
+}
+
+#[cfg(not(debug_assertions))]
+pub fn warn_on_unawaited<F: Future>(call: F) -> F {
+    call
+}
+
 /// `JsCallback`  is a type alias for `ThreadsafeFunction`. It represents a JavaScript function that passed to Rust side.
 /// Related concepts are complex, so we use `JsCallback` to simplify the mental model. For details, please refer to:
 /// - https://napi.rs/docs/compat-mode/concepts/thread-safe-function.en
@@ -40,7 +375,9 @@ fn prettify_type_name(name: &str) -> Cow<str> {
 /// - Js: `(a: string, b: number) => number`
 ///
 /// For `Option<T>` in params position, when you pass `None` in Rust, it will be `null` in Js. However, NAPI-RS generates
-/// `undefined | null | T` as the Js type. So, we introduce `Nullable<T>` in Js to simplify the writing.
+/// `undefined | null | T` as the Js type. So, we introduce `Nullable<T>` in Js to simplify the writing. When the Js side
+/// tells `null` and `undefined` apart, say which one `None` is with [`OrNull<T>`] (`None` is `null`, like `Option<T>`) or
+/// [`OrUndefined<T>`] (`None` is `undefined`, like an omitted argument).
 ///
 /// One tricky part for `Option<T>` in return position is that the corresponding Js type is `T | null | undefined` will make
 /// Ts compiler force you to write `return` statement explicitly. To avoid this, we introduce `VoidNullable<T>` in Js. It will
@@ -83,8 +420,19 @@ fn prettify_type_name(name: &str) -> Cow<str> {
 /// - Rust(Simplified): `MaybeAsyncJsCallback<FnArgs<(Option<String>, i32)>, Option<i32>>`
 /// - Js: `(a: string | null | undefined, b: number) => Promise<number | null | undefined | void> | number | null | undefined | void`
 /// - Js(Simplified): `(a: Nullable<string>, b: number) => MaybePromise<VoidNullable<number>>`
+///
+/// ### Union returns
+///
+/// A Js function that returns one of several shapes maps to `Ret = Either<A, B>`, and works through `await_call` like any
+/// other `Ret`, including when it's wrapped in a promise. NAPI-RS resolves `Either` by trying `A` first and only falling back
+/// to `B` if the value doesn't validate as `A`, so the order matters here too: put the more specific shape first. Objects
+/// generated by `#[napi(object)]` only validate as "is an object", so `Either<ObjA, ObjB>` always resolves to `A` for any
+/// object. Discriminate such values yourself instead.
+///
+/// - Rust: `MaybeAsyncJsCallback<FnArgs<(String,)>, Either<u32, String>>`
+/// - Js: `(a: string) => MaybePromise<number | string>`
 pub type JsCallback<Args, Ret> =
-    Arc<ThreadsafeFunction<Args, Either<Ret, UnknownReturnValue>, Args, false, true>>;
+    Arc<ThreadsafeFunction<Args, Either<Ret, UnknownReturnValue>, Args, Status, false, true>>;
 
 /// Shortcut for `JsCallback<FnArgs<..., Either<Promise<Ret>, Ret>>`, which could be simplified to `MaybeAsyncJsCallback<...>, Ret>`.
 pub type MaybeAsyncJsCallback<Args, Ret> = Arc<
@@ -92,6 +440,7 @@ pub type MaybeAsyncJsCallback<Args, Ret> = Arc<
         Args,
         Either<Either<Promise<Ret>, Ret>, UnknownReturnValue>,
         Args,
+        Status,
         false,
         true,
     >,
@@ -99,73 +448,951 @@ pub type MaybeAsyncJsCallback<Args, Ret> = Arc<
 
 pub trait JsCallbackExt<Args, Ret> {
     fn invoke_async(&self, args: Args) -> impl Future<Output = Result<Ret, napi::Error>> + Send;
+
+    /// Same as `invoke_async`, but blocks the current thread until the Js function has returned,
+    /// for callers that can't `.await`, e.g. a plain thread outside the runtime. Must not be called
+    /// on the JS thread, which would wait on itself forever.
+    fn invoke_blocking(&self, args: Args) -> Result<Ret, napi::Error>;
 }
 
 impl<Args, Ret> JsCallbackExt<Args, Ret> for JsCallback<Args, Ret>
 where
     Args: 'static + Send + JsValuesTupleIntoVec,
-    Ret: 'static + Send + FromNapiValue,
-    napi::Either<Ret, UnknownReturnValue>: FromNapiValue,
+    Ret: 'static + Send + JsCallbackReturn,
 {
-    async fn invoke_async(&self, args: Args) -> Result<Ret, napi::Error> {
-        match self.call_async(args).await? {
+    fn invoke_async(&self, args: Args) -> impl Future<Output = Result<Ret, napi::Error>> + Send {
+        warn_on_unawaited(async move {
+            #[cfg(feature = "metrics")]
+            crate::ffi_latency::queued(self.raw());
+            match until_env_teardown(self.call_async(args)).await? {
+                Either::A(ret) => Ok(ret),
+                Either::B(unknown) => unknown_return::<Ret, Self>(unknown),
+            }
+        })
+    }
+
+    fn invoke_blocking(&self, args: Args) -> Result<Ret, napi::Error> {
+        match call_blocking::<_, _, Self>(self, args)? {
             Either::A(ret) => Ok(ret),
-            Either::B(_unknown) => {
-                // TODO: should provide more information about the unknown return value
-                let js_type = "unknown";
+            Either::B(unknown) => unknown_return::<Ret, Self>(unknown),
+        }
+    }
+}
 
-                Err(napi::Error::new(
-                    napi::Status::InvalidArg,
-                    format!("UNKNOWN_RETURN_VALUE. Cannot convert {js_type} to `` in ",),
-                ))
-            }
+/// Queue a call to `tsfn` and block the current thread until it has returned, see
+/// [`JsCallbackExt::invoke_blocking`]. `C` names the callback in errors.
+fn call_blocking<Args, Return, C: ?Sized>(
+    tsfn: &ThreadsafeFunction<Args, Return, Args, Status, false, true>,
+    args: Args,
+) -> Result<Return, napi::Error>
+where
+    Args: 'static + JsValuesTupleIntoVec,
+    Return: 'static + Send + FromNapiValue,
+{
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    #[cfg(feature = "metrics")]
+    crate::ffi_latency::queued(tsfn.raw());
+    let status =
+        tsfn.call_with_return_value(args, ThreadsafeFunctionCallMode::Blocking, move |ret, _| {
+            let _ = tx.send(ret);
+            Ok(())
+        });
+    if status != Status::Ok {
+        return Err(napi::Error::new(
+            status,
+            format!(
+                "BLOCKING_CALL_FAILED. Couldn't queue a call to {}.",
+                pretty_type_name::<C>()
+            ),
+        ));
+    }
+    // The sender is only dropped unused when the environment is torn down before the call ran.
+    rx.recv().unwrap_or_else(|_| {
+        Err(napi::Error::new(
+            Status::Cancelled,
+            "RUNTIME_EXITING. The Node environment was torn down while awaiting a JS call.",
+        ))
+    })
+}
+
+/// Synchronous counterpart of [`JsCallbackExt`] for a plain `Function`, which skips the threadsafe function machinery
+/// entirely.
+///
+/// A `Function` is only valid on the JS thread, inside the scope it was received in, so `invoke` must only be called from
+/// there, e.g. from a sync `#[napi]` function the callback was passed to. Use `JsCallback` for anything that runs on
+/// another thread or after the scope has ended.
+///
+/// The method is named `invoke` rather than `call` because `Function::call` already exists and would shadow it.
+pub trait JsFunctionExt<Args, Ret> {
+    fn invoke(&self, args: Args) -> Result<Ret, napi::Error>;
+}
+
+impl<Args, Ret> JsFunctionExt<Args, Ret> for Function<'_, Args, Either<Ret, UnknownReturnValue>>
+where
+    Args: JsValuesTupleIntoVec,
+    Ret: 'static + JsCallbackReturn,
+{
+    fn invoke(&self, args: Args) -> Result<Ret, napi::Error> {
+        match self.call(args)? {
+            Either::A(ret) => Ok(ret),
+            Either::B(unknown) => unknown_return::<Ret, Self>(unknown),
+        }
+    }
+}
+
+/// An `Option<T>` argument that is passed as `null` when it's `None`, exactly like `Option<T>` itself. Spells out the
+/// intent next to [`OrUndefined<T>`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OrNull<T>(pub Option<T>);
+
+/// An `Option<T>` argument that is passed as `undefined` when it's `None`, e.g. for a Js function checking
+/// `arg === undefined` or relying on a default parameter value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OrUndefined<T>(pub Option<T>);
+
+impl<T> From<Option<T>> for OrNull<T> {
+    fn from(value: Option<T>) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> From<Option<T>> for OrUndefined<T> {
+    fn from(value: Option<T>) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: ToNapiValue> ToNapiValue for OrNull<T> {
+    unsafe fn to_napi_value(env: sys::napi_env, val: Self) -> napi::Result<sys::napi_value> {
+        unsafe { Option::<T>::to_napi_value(env, val.0) }
+    }
+}
+
+impl<T: ToNapiValue> ToNapiValue for OrUndefined<T> {
+    unsafe fn to_napi_value(env: sys::napi_env, val: Self) -> napi::Result<sys::napi_value> {
+        match val.0 {
+            Some(value) => unsafe { T::to_napi_value(env, value) },
+            None => unsafe { <()>::to_napi_value(env, ()) },
+        }
+    }
+}
+
+/// A Node-style error-first callback, `(err: BindingError | null, result: T) => void`.
+pub type NodebackJsCallback<T> = JsCallback<FnArgs<(Option<BindingError>, T)>, ()>;
+
+pub trait NodebackExt<T> {
+    /// Call the function with `err` in the first position, following Node's error-first convention. `None` is passed as
+    /// `null`.
+    fn call_nodeback(
+        &self,
+        err: Option<BindingError>,
+        result: T,
+    ) -> impl Future<Output = Result<(), napi::Error>> + Send;
+}
+
+impl<T> NodebackExt<T> for NodebackJsCallback<T>
+where
+    T: 'static + Send + ToNapiValue,
+{
+    async fn call_nodeback(&self, err: Option<BindingError>, result: T) -> Result<(), napi::Error> {
+        self.invoke_async(FnArgs::from((err, result))).await
+    }
+}
+
+/// How a `MaybeAsyncJsCallback` handed back its value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReturnMode {
+    /// The Js function returned the value (or nothing) directly.
+    Sync,
+    /// The Js function returned a promise, which was awaited.
+    Promise,
+}
+
+/// What a `MaybeAsyncJsCallback` handed back, before any promise is awaited. A flat take on the
+/// `Either<Either<Promise<Ret>, Ret>, UnknownReturnValue>` napi converts the return value into.
+pub enum CallbackReturn<Ret: 'static + FromNapiValue> {
+    /// The Js function returned a promise, not awaited yet.
+    Promise(Promise<Ret>),
+    /// The Js function returned a value that converted to `Ret`.
+    Value(Ret),
+    /// The Js function returned something that is neither, e.g. `undefined` for a non-`()` `Ret`.
+    Unknown(UnknownReturnValue),
+}
+
+impl<Ret: 'static + FromNapiValue> From<Either<Either<Promise<Ret>, Ret>, UnknownReturnValue>>
+    for CallbackReturn<Ret>
+{
+    fn from(value: Either<Either<Promise<Ret>, Ret>, UnknownReturnValue>) -> Self {
+        match value {
+            Either::A(Either::A(promise)) => Self::Promise(promise),
+            Either::A(Either::B(ret)) => Self::Value(ret),
+            Either::B(unknown) => Self::Unknown(unknown),
+        }
+    }
+}
+
+impl<Ret: 'static + FromNapiValue> From<CallbackReturn<Ret>>
+    for Either<Either<Promise<Ret>, Ret>, UnknownReturnValue>
+{
+    fn from(value: CallbackReturn<Ret>) -> Self {
+        match value {
+            CallbackReturn::Promise(promise) => Either::A(Either::A(promise)),
+            CallbackReturn::Value(ret) => Either::A(Either::B(ret)),
+            CallbackReturn::Unknown(unknown) => Either::B(unknown),
         }
     }
 }
 
 pub trait MaybeAsyncJsCallbackExt<Args, Ret> {
+    /// Call the Js function and hand back what it returned as is, without awaiting a returned
+    /// promise or treating an unknown return value as an error.
+    fn call_raw(
+        &self,
+        args: Args,
+    ) -> impl Future<Output = Result<CallbackReturn<Ret>, napi::Error>> + Send
+    where
+        Ret: 'static + FromNapiValue;
+
     /// Call Js function asynchronously in rust. If the Js function returns `Promise<T>`, it will unwrap/await the promise and return `T`.
     fn await_call(&self, args: Args) -> impl Future<Output = Result<Ret, napi::Error>> + Send;
+
+    /// Same as `await_call`, but also reports whether the Js function returned its value directly
+    /// or through a promise.
+    fn await_call_detailed(
+        &self,
+        args: Args,
+    ) -> impl Future<Output = Result<(Ret, ReturnMode), napi::Error>> + Send;
+
+    /// Same as `await_call`, but only accepts synchronous returns. If the Js function returns a
+    /// promise, fails with `ASYNC_NOT_ALLOWED` without awaiting it.
+    fn try_await_call(&self, args: Args) -> impl Future<Output = Result<Ret, napi::Error>> + Send;
+
+    /// Same as `await_call`, but fails with `CALLBACK_TIMED_OUT` if the promise the Js function
+    /// returned hasn't settled within `timeout`, rather than waiting forever. A value returned
+    /// directly is never timed.
+    fn await_call_timeout(
+        &self,
+        args: Args,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send {
+        self.await_call_timeout_on(args, timeout, &TokioClock)
+    }
+
+    /// `await_call_timeout`, measuring `timeout` on `clock`.
+    fn await_call_timeout_on(
+        &self,
+        args: Args,
+        timeout: Duration,
+        clock: &dyn Clock,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send;
+
+    /// Same as `await_call`, but calls the Js function again when it fails, up to `attempts` calls
+    /// in total (at least one), sleeping `backoff * n` after the `n`th failure. Returns the first
+    /// success, or the last error once every attempt failed. Every attempt gets its own clone of
+    /// `args`, hence the `Clone` bound.
+    fn await_call_retry(
+        &self,
+        args: Args,
+        attempts: u32,
+        backoff: Duration,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send
+    where
+        Args: Clone,
+    {
+        self.await_call_retry_on(args, attempts, backoff, &TokioClock)
+    }
+
+    /// `await_call_retry`, sleeping on `clock` between attempts.
+    fn await_call_retry_on(
+        &self,
+        args: Args,
+        attempts: u32,
+        backoff: Duration,
+        clock: &dyn Clock,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send
+    where
+        Args: Clone;
+
+    /// Same as `await_call`, but gives up with `CANCELLED` once `token` is cancelled, e.g. on
+    /// shutdown, rather than leaving the call pending. The Js side keeps running, but whatever it
+    /// returns is ignored. The Js function isn't called at all if `token` is already cancelled.
+    /// Clones of one token can cancel any number of calls at once.
+    fn await_call_cancellable(
+        &self,
+        args: Args,
+        token: CancellationToken,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send;
+
+    /// Call the Js function once per element of `args`, all at once rather than one after the other,
+    /// and await every call. The results are in the order of `args`. Fails with the first error in
+    /// that order, but only once every call has settled; see `try_await_call_many` to keep the
+    /// results of the calls that succeeded.
+    fn await_call_many(
+        &self,
+        args: Vec<Args>,
+    ) -> impl Future<Output = Result<Vec<Ret>, napi::Error>> + Send;
+
+    /// Same as `await_call_many`, but reports the result of every call, so one failing call doesn't
+    /// discard the others. Unlike `try_await_call`, promises are awaited.
+    fn try_await_call_many(
+        &self,
+        args: Vec<Args>,
+    ) -> impl Future<Output = Vec<Result<Ret, napi::Error>>> + Send;
+
+    /// Queue a call to the Js function without blocking and without waiting for it to run. The
+    /// result only tells whether the call was queued; whatever the function returns or throws is
+    /// ignored. A call that couldn't be queued, e.g. because the queue is full or the function was
+    /// released, is logged as a warning.
+    fn notify(&self, args: Args) -> napi::Result<()>;
+
+    /// Same as `try_await_call`, but blocks the current thread until the Js function has returned,
+    /// see `JsCallbackExt::invoke_blocking`. A returned promise fails with `ASYNC_NOT_ALLOWED`, as
+    /// there's no runtime to await it with.
+    fn invoke_blocking(&self, args: Args) -> napi::Result<Ret>;
+
+    /// Same as `await_call`, but passes any error through `f` first, e.g. to add app-specific context.
+    fn await_call_map_err<F>(
+        &self,
+        args: Args,
+        f: F,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send
+    where
+        F: FnOnce(napi::Error) -> napi::Error + Send;
+
+    /// Same as `await_call`, but runs `validate` on `args` first. If it fails, its error is
+    /// returned as is and the Js function is never called.
+    fn await_call_validated<F>(
+        &self,
+        args: Args,
+        validate: F,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send
+    where
+        F: FnOnce(&Args) -> Result<(), napi::Error> + Send;
 }
 impl<Args, Ret> MaybeAsyncJsCallbackExt<Args, Ret> for JsCallback<Args, Either<Promise<Ret>, Ret>>
 where
     Args: 'static + Send + JsValuesTupleIntoVec,
-    Ret: 'static + Send + FromNapiValue,
-    napi::Either<napi::Either<Promise<Ret>, Ret>, UnknownReturnValue>: FromNapiValue,
+    Ret: 'static + Send + JsCallbackReturn,
 {
     #[allow(clippy::manual_async_fn)]
-    fn await_call(&self, args: Args) -> impl Future<Output = Result<Ret, napi::Error>> + Send {
+    fn call_raw(
+        &self,
+        args: Args,
+    ) -> impl Future<Output = Result<CallbackReturn<Ret>, napi::Error>> + Send
+    where
+        Ret: 'static + FromNapiValue,
+    {
         async move {
-            println!("Calling JavaScript function with args");
-            match self.call_async(args).await {
+            #[cfg(feature = "metrics")]
+            crate::ffi_latency::queued(self.raw());
+            until_env_teardown(self.call_async(args))
+                .await
+                .map(CallbackReturn::from)
+        }
+    }
+
+    fn await_call(&self, args: Args) -> impl Future<Output = Result<Ret, napi::Error>> + Send {
+        warn_on_unawaited(async move { self.await_call_detailed(args).await.map(|(ret, _)| ret) })
+    }
+
+    fn await_call_detailed(
+        &self,
+        args: Args,
+    ) -> impl Future<Output = Result<(Ret, ReturnMode), napi::Error>> + Send {
+        warn_on_unawaited(async move {
+            diag_trace!("Calling JavaScript function with args");
+            match self.call_raw(args).await {
                 Ok(result) => match result {
-                    Either::A(Either::A(promise)) => {
-                        println!("JavaScript function returned a promise, awaiting the promise");
-                        promise.await
+                    CallbackReturn::Promise(promise) => {
+                        diag!("JavaScript function returned a promise, awaiting the promise");
+                        until_env_teardown(promise)
+                            .await
+                            .map(|ret| (ret, ReturnMode::Promise))
                     }
-                    Either::A(Either::B(ret)) => {
-                        println!("JavaScript function returned a value");
-                        Ok(ret)
+                    CallbackReturn::Value(ret) => {
+                        diag!("JavaScript function returned a value");
+                        Ok((ret, ReturnMode::Sync))
                     }
-                    Either::B(_unknown) => {
-                        let js_type = "unknown";
-                        let expected_rust_type = pretty_type_name::<Ret>();
-                        println!("Unknown return value from JavaScript function");
-
-                        Err(napi::Error::new(
-                            napi::Status::InvalidArg,
-                            format!(
-                "UNKNOWN_RETURN_VALUE. Cannot convert {js_type} to `{expected_rust_type}` in {}.",
-                pretty_type_name::<Self>(),
-              ),
-                        ))
+                    CallbackReturn::Unknown(unknown) => {
+                        diag!("Unknown return value from JavaScript function");
+                        unknown_return::<Ret, Self>(unknown).map(|ret| (ret, ReturnMode::Sync))
                     }
                 },
                 Err(e) => {
-                    println!("Error calling JavaScript function: {:?}", e);
+                    diag_err!("Error calling JavaScript function: {:?}", e);
                     Err(e)
                 }
             }
+        })
+    }
+
+    fn try_await_call(&self, args: Args) -> impl Future<Output = Result<Ret, napi::Error>> + Send {
+        warn_on_unawaited(async move {
+            match self.call_raw(args).await? {
+                CallbackReturn::Promise(_promise) => Err(napi::Error::new(
+                    napi::Status::InvalidArg,
+                    format!(
+                        "ASYNC_NOT_ALLOWED. Expected {} to return synchronously, got a promise.",
+                        pretty_type_name::<Self>(),
+                    ),
+                )),
+                CallbackReturn::Value(ret) => Ok(ret),
+                CallbackReturn::Unknown(unknown) => unknown_return::<Ret, Self>(unknown),
+            }
+        })
+    }
+
+    fn await_call_timeout_on(
+        &self,
+        args: Args,
+        timeout: Duration,
+        clock: &dyn Clock,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send {
+        warn_on_unawaited(async move {
+            match self.call_raw(args).await? {
+                CallbackReturn::Promise(promise) => {
+                    let settled = pin!(until_env_teardown(promise));
+                    match select(settled, clock.sleep(timeout)).await {
+                        future::Either::Left((settled, _)) => settled,
+                        future::Either::Right(((), _)) => Err(napi::Error::new(
+                            napi::Status::GenericFailure,
+                            format!(
+                                "CALLBACK_TIMED_OUT. The promise returned by {} didn't settle within {timeout:?}.",
+                                pretty_type_name::<Self>(),
+                            ),
+                        )),
+                    }
+                }
+                CallbackReturn::Value(ret) => Ok(ret),
+                CallbackReturn::Unknown(unknown) => unknown_return::<Ret, Self>(unknown),
+            }
+        })
+    }
+
+    #[allow(clippy::manual_async_fn)]
+    fn await_call_retry_on(
+        &self,
+        args: Args,
+        attempts: u32,
+        backoff: Duration,
+        clock: &dyn Clock,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send
+    where
+        Args: Clone,
+    {
+        retry(attempts, backoff, clock, move || {
+            self.await_call(args.clone())
+        })
+    }
+
+    #[allow(clippy::manual_async_fn)]
+    fn await_call_cancellable(
+        &self,
+        args: Args,
+        token: CancellationToken,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send {
+        async move { until_cancelled::<_, Self, _>(&token, || self.await_call(args)).await }
+    }
+
+    #[allow(clippy::manual_async_fn)]
+    fn await_call_many(
+        &self,
+        args: Vec<Args>,
+    ) -> impl Future<Output = Result<Vec<Ret>, napi::Error>> + Send {
+        async move { self.try_await_call_many(args).await.into_iter().collect() }
+    }
+
+    #[allow(clippy::manual_async_fn)]
+    fn try_await_call_many(
+        &self,
+        args: Vec<Args>,
+    ) -> impl Future<Output = Vec<Result<Ret, napi::Error>>> + Send {
+        async move { join_all(args.into_iter().map(|args| self.await_call(args))).await }
+    }
+
+    fn notify(&self, args: Args) -> napi::Result<()> {
+        #[cfg(feature = "metrics")]
+        crate::ffi_latency::queued(self.raw());
+        let status =
+            self.call_with_return_value(args, ThreadsafeFunctionCallMode::NonBlocking, |_, _| {
+                Ok(())
+            });
+        if status == Status::Ok {
+            return Ok(());
+        }
+        if !crate::is_quiet() {
+            tracing::warn!(
+                "couldn't queue a call to {}: {status}",
+                pretty_type_name::<Self>()
+            );
+        }
+        Err(napi::Error::new(
+            status,
+            format!(
+                "NOTIFY_FAILED. Couldn't queue a call to {}.",
+                pretty_type_name::<Self>()
+            ),
+        ))
+    }
+
+    fn invoke_blocking(&self, args: Args) -> napi::Result<Ret> {
+        match CallbackReturn::from(call_blocking::<_, _, Self>(self, args)?) {
+            CallbackReturn::Promise(_promise) => Err(napi::Error::new(
+                napi::Status::InvalidArg,
+                format!(
+                    "ASYNC_NOT_ALLOWED. Cannot block on async callback {}, which returned a promise.",
+                    pretty_type_name::<Self>(),
+                ),
+            )),
+            CallbackReturn::Value(ret) => Ok(ret),
+            CallbackReturn::Unknown(unknown) => unknown_return::<Ret, Self>(unknown),
+        }
+    }
+
+    #[allow(clippy::manual_async_fn)]
+    fn await_call_map_err<F>(
+        &self,
+        args: Args,
+        f: F,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send
+    where
+        F: FnOnce(napi::Error) -> napi::Error + Send,
+    {
+        async move { self.await_call(args).await.map_err(f) }
+    }
+
+    #[allow(clippy::manual_async_fn)]
+    fn await_call_validated<F>(
+        &self,
+        args: Args,
+        validate: F,
+    ) -> impl Future<Output = Result<Ret, napi::Error>> + Send
+    where
+        F: FnOnce(&Args) -> Result<(), napi::Error> + Send,
+    {
+        async move {
+            validate(&args)?;
+            self.await_call(args).await
+        }
+    }
+}
+
+/// Caches the successful results of a `MaybeAsyncJsCallback` keyed by its arguments, so repeated
+/// calls with identical args within `ttl` don't cross into JS again. Errors are never cached.
+///
+/// Only use this for callbacks that are idempotent from the Rust side's point of view.
+pub struct Memoized<Args, Ret>
+where
+    Args: 'static + JsValuesTupleIntoVec,
+    Ret: 'static + JsCallbackReturn,
+{
+    callback: MaybeAsyncJsCallback<Args, Ret>,
+    ttl: Duration,
+    cache: Mutex<HashMap<Args, (Instant, Ret)>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<Args, Ret> Memoized<Args, Ret>
+where
+    Args: 'static + Send + JsValuesTupleIntoVec + Hash + Eq + Clone,
+    Ret: 'static + Send + JsCallbackReturn + Clone,
+{
+    pub fn new(callback: MaybeAsyncJsCallback<Args, Ret>, ttl: Duration) -> Self {
+        Self {
+            callback,
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+            clock: Arc::new(TokioClock),
+        }
+    }
+
+    /// Measure `ttl` on `clock` instead of the wall clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Same as [`MaybeAsyncJsCallbackExt::await_call`], but returns the cached result if `args`
+    /// was seen less than `ttl` ago.
+    pub async fn await_call(&self, args: Args) -> Result<Ret, napi::Error> {
+        if let Some(ret) = self.cached(&args) {
+            return Ok(ret);
+        }
+        let ret = self.callback.await_call(args.clone()).await?;
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(args, (self.clock.now(), ret.clone()));
+        Ok(ret)
+    }
+
+    fn cached(&self, args: &Args) -> Option<Ret> {
+        let mut cache = self.cache.lock().unwrap();
+        match cache.get(args) {
+            Some((at, ret)) if self.clock.now().duration_since(*at) < self.ttl => Some(ret.clone()),
+            Some(_) => {
+                cache.remove(args);
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+/// A `MaybeAsyncJsCallback` that survives its threadsafe function being released, e.g. when the
+/// JS side reloads the module that registered it during HMR. A call failing because the callback
+/// is closing asks `reconnect` for a fresh one and is retried once with it. The fresh callback is
+/// kept for the calls that follow.
+pub struct ReconnectingCallback<Args, Ret>
+where
+    Args: 'static + JsValuesTupleIntoVec,
+    Ret: 'static + JsCallbackReturn,
+{
+    callback: Mutex<MaybeAsyncJsCallback<Args, Ret>>,
+    reconnect: Box<dyn Fn() -> MaybeAsyncJsCallback<Args, Ret> + Send + Sync>,
+}
+
+impl<Args, Ret> ReconnectingCallback<Args, Ret>
+where
+    Args: 'static + Send + JsValuesTupleIntoVec + Clone,
+    Ret: 'static + Send + JsCallbackReturn,
+{
+    pub fn new(
+        callback: MaybeAsyncJsCallback<Args, Ret>,
+        reconnect: impl Fn() -> MaybeAsyncJsCallback<Args, Ret> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            callback: Mutex::new(callback),
+            reconnect: Box::new(reconnect),
+        }
+    }
+
+    /// Same as [`MaybeAsyncJsCallbackExt::await_call`], but reconnects and retries once if the
+    /// callback was released.
+    pub async fn await_call(&self, args: Args) -> Result<Ret, napi::Error> {
+        let callback = Arc::clone(&self.callback.lock().unwrap());
+        match callback.await_call(args.clone()).await {
+            Err(e) if e.status == Status::Closing => {
+                let fresh = (self.reconnect)();
+                *self.callback.lock().unwrap() = Arc::clone(&fresh);
+                fresh.await_call(args).await
+            }
+            result => result,
+        }
+    }
+}
+
+/// Called with the type name of a [`Tracked`] callback, see [`set_lifecycle_hooks`].
+pub type LifecycleHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct LifecycleHooks {
+    /// Called when a callback is received from JS, which acquires its threadsafe function.
+    pub on_acquire: Option<LifecycleHook>,
+    /// Called when a callback is dropped, which releases its threadsafe function.
+    pub on_release: Option<LifecycleHook>,
+}
+
+static LIFECYCLE_HOOKS: LazyLock<RwLock<LifecycleHooks>> = LazyLock::new(RwLock::default);
+
+/// Install the hooks every [`Tracked`] callback reports to, replacing the previous ones. This is
+/// meant for debugging reference-count leaks, e.g. by logging each acquire and release.
+pub fn set_lifecycle_hooks(hooks: LifecycleHooks) {
+    *LIFECYCLE_HOOKS.write().unwrap() = hooks;
+}
+
+/// A callback (usually a `JsCallback` or `MaybeAsyncJsCallback`) that reports to the
+/// [`LifecycleHooks`] when it's received from JS and when it's dropped. It derefs to the
+/// callback, so it's called the same way.
+///
+/// The threadsafe function is only released once every clone of the callback is gone, so clone
+/// the `Tracked` itself (e.g. by putting it in an `Arc`) rather than the callback inside.
+pub struct Tracked<C>(C);
+
+impl<C> Deref for Tracked<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.0
+    }
+}
+
+impl<C: TypeName> TypeName for Tracked<C> {
+    fn type_name() -> &'static str {
+        C::type_name()
+    }
+
+    fn value_type() -> napi::ValueType {
+        C::value_type()
+    }
+}
+
+impl<C: FromNapiValue> FromNapiValue for Tracked<C> {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> napi::Result<Self> {
+        let callback = C::from_napi_value(env, napi_val)?;
+        let on_acquire = LIFECYCLE_HOOKS.read().unwrap().on_acquire.clone();
+        if let Some(on_acquire) = on_acquire {
+            on_acquire(&pretty_type_name::<C>());
+        }
+        Ok(Self(callback))
+    }
+}
+
+impl<C> Drop for Tracked<C> {
+    fn drop(&mut self) {
+        let on_release = LIFECYCLE_HOOKS.read().unwrap().on_release.clone();
+        if let Some(on_release) = on_release {
+            on_release(&pretty_type_name::<C>());
+        }
+    }
+}
+
+/// A `#[napi]` class instance returned from JS, as an owned clone of the Rust struct behind it.
+///
+/// A `ClassInstance<T>` or `&T` borrows the object on the JS thread, so neither can be the `Ret`
+/// of a callback, whose value is handed to whichever thread awaits the call. Use `Cloned<T>`
+/// instead: `T` is cloned out on the JS thread as soon as the value is converted, including when
+/// it's the resolved value of a returned promise. The clone is independent from then on, so
+/// changes to it or to the JS object aren't seen by the other, and the object itself stays owned
+/// by JS and is garbage collected as usual.
+pub struct Cloned<T>(pub T);
+
+impl<T> Deref for Cloned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: 'static> TypeName for Cloned<T>
+where
+    &'static T: TypeName,
+{
+    fn type_name() -> &'static str {
+        <&T>::type_name()
+    }
+
+    fn value_type() -> napi::ValueType {
+        <&T>::value_type()
+    }
+}
+
+impl<T: 'static> ValidateNapiValue for Cloned<T>
+where
+    &'static T: ValidateNapiValue,
+{
+    unsafe fn validate(
+        env: sys::napi_env,
+        napi_val: sys::napi_value,
+    ) -> napi::Result<sys::napi_value> {
+        <&T>::validate(env, napi_val)
+    }
+}
+
+impl<T: 'static + MaybeTypeTag + Clone> FromNapiValue for Cloned<T> {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> napi::Result<Self> {
+        ClassInstance::<T>::from_napi_value(env, napi_val).map(|instance| Self((*instance).clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn environment_loaded_after_the_last_one_exited_can_await_calls() {
+        env_loaded();
+        env_torn_down();
+        assert!(until_env_teardown(async { Ok(()) }).await.is_err());
+
+        env_loaded();
+        assert!(until_env_teardown(async { Ok(()) }).await.is_ok());
+        env_torn_down();
+    }
+
+    type Nested = Either<Either<Promise<u32>, u32>, UnknownReturnValue>;
+
+    #[test]
+    fn callback_return_converts_to_and_from_the_nested_either() {
+        let value = CallbackReturn::from(Nested::A(Either::B(7)));
+        assert!(matches!(value, CallbackReturn::Value(7)));
+        assert!(matches!(Nested::from(value), Either::A(Either::B(7))));
+
+        let unknown = UnknownReturnValue(ValueType::Object);
+        let value = CallbackReturn::<u32>::from(Nested::B(unknown));
+        assert!(matches!(value, CallbackReturn::Unknown(u) if u == unknown));
+        assert!(matches!(Nested::from(value), Either::B(u) if u.js_type() == "object"));
+    }
+
+    #[test]
+    fn pretty_type_name_shortens_nested_paths() {
+        assert_eq!(
+            pretty_type_name::<FnArgs<(Option<String>, i32)>>(),
+            "FnArgs<(Option<String>, i32)>"
+        );
+        assert_eq!(
+            pretty_type_name::<Promise<Vec<String>>>(),
+            "Promise<Vec<String>>"
+        );
+        assert_eq!(pretty_type_name::<&[std::path::PathBuf]>(), "&[PathBuf]");
+    }
+
+    #[test]
+    fn pretty_type_name_is_shortened_once_per_type() {
+        let first = pretty_type_name::<Either<String, Vec<u8>>>();
+        let second = pretty_type_name::<Either<String, Vec<u8>>>();
+        let (Cow::Borrowed(first), Cow::Borrowed(second)) = (first, second) else {
+            panic!("not served from the cache");
+        };
+        assert_eq!(first, "Either<String, Vec<u8>>");
+        assert!(std::ptr::eq(first, second));
+    }
+
+    /// What `f` logs at `WARN` and above.
+    fn warnings(f: impl FnOnce()) -> String {
+        #[derive(Clone, Default)]
+        struct Buf(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Buf {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buf = Buf::default();
+        let writer = buf.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::WARN)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+        let logged = buf.0.lock().unwrap().clone();
+        String::from_utf8(logged).unwrap()
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn only_a_call_dropped_before_its_first_poll_is_warned_about() {
+        let dropped = warnings(|| drop(warn_on_unawaited(async { 1 })));
+        assert!(
+            dropped.contains("callback future dropped without being awaited"),
+            "{dropped}"
+        );
+
+        let awaited = warnings(|| {
+            assert_eq!(
+                futures::executor::block_on(warn_on_unawaited(async { 1 })),
+                1
+            );
+        });
+        assert_eq!(awaited, "");
+
+        // Polled but still pending, like a call raced against a timeout.
+        let cancelled = warnings(|| {
+            let mut call = warn_on_unawaited(futures::future::pending::<()>());
+            let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+            assert!(Pin::new(&mut call).poll(&mut cx).is_pending());
+        });
+        assert_eq!(cancelled, "");
+    }
+
+    #[test]
+    fn call_with_a_cancelled_token_fails_without_being_started() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let started = std::cell::Cell::new(false);
+        let logged = warnings(|| {
+            let call = until_cancelled::<u32, (), _>(&token, || {
+                started.set(true);
+                warn_on_unawaited(async { Ok(1) })
+            });
+            let e = futures::executor::block_on(call).unwrap_err();
+            assert_eq!(e.status, Status::Cancelled);
+            assert!(e.reason.starts_with("CANCELLED. "), "{}", e.reason);
+        });
+        assert!(!started.get());
+        assert_eq!(logged, "");
+    }
+
+    #[test]
+    fn call_with_a_live_token_settles_as_usual() {
+        let token = CancellationToken::new();
+        let call = until_cancelled::<u32, (), _>(&token, || async { Ok(1) });
+        assert_eq!(futures::executor::block_on(call).unwrap(), 1);
+    }
+
+    async fn run_tasks() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
         }
     }
+
+    /// A callback that fails its first `failures` calls, counting them in `calls`.
+    fn flaky(
+        calls: &Arc<std::sync::atomic::AtomicU32>,
+        failures: u32,
+    ) -> impl FnMut() -> futures::future::Ready<napi::Result<u32>> + Send + 'static {
+        let calls = Arc::clone(calls);
+        move || {
+            let call = calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            futures::future::ready(if call > failures {
+                Ok(call)
+            } else {
+                Err(napi::Error::from_reason(format!("failure {call}")))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_backs_off_between_failures_until_a_call_succeeds() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let clock = Arc::new(crate::clock::MockClock::new());
+        let calls = Arc::new(AtomicU32::new(0));
+        let attempt = flaky(&calls, 2);
+        let sleeper = Arc::clone(&clock);
+        let retried =
+            tokio::spawn(
+                async move { retry(5, Duration::from_millis(100), &*sleeper, attempt).await },
+            );
+
+        run_tasks().await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        clock.advance(Duration::from_millis(99));
+        run_tasks().await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        clock.advance(Duration::from_millis(1));
+        run_tasks().await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        // The second failure backs off twice as long.
+        clock.advance(Duration::from_millis(100));
+        run_tasks().await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        clock.advance(Duration::from_millis(100));
+        run_tasks().await;
+
+        assert_eq!(retried.await.unwrap().unwrap(), 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_returns_the_last_error_once_every_attempt_failed() {
+        let calls = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let e = retry(
+            3,
+            Duration::ZERO,
+            &crate::clock::MockClock::new(),
+            flaky(&calls, 5),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(e.reason, "failure 3");
+    }
 }
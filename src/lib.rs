@@ -1,20 +1,109 @@
+/// `tracing::debug!` that stays silent when `NAPI_BIND_QUIET` is set, see [`is_quiet`].
+macro_rules! diag {
+    ($($arg:tt)*) => {
+        if !$crate::is_quiet() {
+            tracing::debug!($($arg)*);
+        }
+    };
+}
+
+/// `tracing::trace!` that stays silent when `NAPI_BIND_QUIET` is set, see [`is_quiet`].
+macro_rules! diag_trace {
+    ($($arg:tt)*) => {
+        if !$crate::is_quiet() {
+            tracing::trace!($($arg)*);
+        }
+    };
+}
+
+/// `tracing::error!` that stays silent when `NAPI_BIND_QUIET` is set, see [`is_quiet`]. Repeats of
+/// the same message are throttled into a periodic count, see [`error_throttle`].
+macro_rules! diag_err {
+    ($($arg:tt)*) => {
+        if !$crate::is_quiet() {
+            if let Some(message) = $crate::error_throttle::throttled(format!($($arg)*)) {
+                tracing::error!("{message}");
+            }
+        }
+    };
+}
+
+pub mod async_iter;
+pub mod bigint;
+pub mod clock;
+pub mod collections;
+#[cfg(feature = "dev")]
+pub mod dev;
+pub mod emitter;
+mod error_throttle;
+pub mod event_log;
+#[cfg(feature = "metrics")]
+mod ffi_latency;
+mod fs_watch;
 pub mod js_call;
+mod rate_limit;
+pub mod tagged;
+pub mod transport;
+pub mod watcher_core;
 
-use std::{fs, future::Future, sync::Arc, thread};
+use std::{
+    any::TypeId,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    future::Future,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    panic::AssertUnwindSafe,
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, LazyLock, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
 
-use js_call::MaybeAsyncJsCallbackExt;
+use clock::{Clock, TokioClock};
+use emitter::Emitter;
+use event_log::EventLog;
+use fs_watch::{FsEvent, WatchSource};
+use futures::{
+    channel::mpsc::UnboundedReceiver,
+    future::{self, Either as Raced},
+    FutureExt, Stream, StreamExt,
+};
+use js_call::{MaybeAsyncJsCallbackExt, UnknownReturnValue};
 use napi::{
-    bindgen_prelude::{FnArgs, Promise},
-    threadsafe_function::{ThreadsafeFunction, UnknownReturnValue},
-    Either,
+    bindgen_prelude::{
+        FromNapiValue, JsValuesTupleIntoVec, Object, Promise, PromiseRaw, TypeName, Unknown,
+    },
+    sys,
+    threadsafe_function::ThreadsafeFunction,
+    Either, Env, Status, ValueType,
 };
 use napi_derive::napi;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use transport::Transport;
+use watcher_core::WatcherCore;
+
+static QUIET: LazyLock<bool> = LazyLock::new(|| {
+    std::env::var("NAPI_BIND_QUIET").is_ok_and(|value| value == "1" || value == "true")
+});
+
+/// Whether `NAPI_BIND_QUIET=1` was set when the crate first checked. All diagnostic output is
+/// suppressed in that case, whatever sink is configured. The variable is only read once.
+pub fn is_quiet() -> bool {
+    *QUIET
+}
 
 pub type MaybeAsyncJsCallback<Args, Ret> = Arc<
     ThreadsafeFunction<
         Args,
         Either<Either<Promise<Ret>, Ret>, UnknownReturnValue>,
         Args,
+        Status,
         false,
         true,
     >,
@@ -22,28 +111,95 @@ pub type MaybeAsyncJsCallback<Args, Ret> = Arc<
 
 use std::fmt::Display;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum WatcherEvent {
     Close,
     Event(BundleEvent),
     ReStart,
+    Change(WatcherChangeData),
+    ChangeBatch(Vec<WatcherChangeData>),
+    /// The watcher's stats, delivered right before `Close` when `summary_on_close` is set.
+    Summary(BindingWatcherStats),
+    /// An event of a type registered by a plugin, see `BindingWatcher::register_event_type`.
+    /// `payload` is its JSON serialization.
+    Custom {
+        name: String,
+        payload: String,
+    },
+}
+
+impl WatcherEvent {
+    pub fn kind(&self) -> WatcherEventKind {
+        match self {
+            WatcherEvent::Close => WatcherEventKind::Close,
+            WatcherEvent::Event(_) => WatcherEventKind::Event,
+            WatcherEvent::ReStart => WatcherEventKind::ReStart,
+            WatcherEvent::Change(_) => WatcherEventKind::Change,
+            WatcherEvent::ChangeBatch(_) => WatcherEventKind::ChangeBatch,
+            WatcherEvent::Summary(_) => WatcherEventKind::Summary,
+            WatcherEvent::Custom { .. } => WatcherEventKind::Custom,
+        }
+    }
 }
 
 impl Display for WatcherEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.kind().fmt(f)
+    }
+}
+
+/// A `WatcherEvent` without its payload, e.g. to subscribe to some kinds of events only.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum WatcherEventKind {
+    Close,
+    Event,
+    ReStart,
+    Change,
+    ChangeBatch,
+    Summary,
+    Custom,
+}
+
+impl WatcherEventKind {
+    /// Parse the name `eventKind()` reports for this kind of event.
+    pub fn from_name(name: &str) -> napi::Result<Self> {
+        match name {
+            "close" => Ok(WatcherEventKind::Close),
+            "event" => Ok(WatcherEventKind::Event),
+            "restart" => Ok(WatcherEventKind::ReStart),
+            "change" => Ok(WatcherEventKind::Change),
+            "change_batch" => Ok(WatcherEventKind::ChangeBatch),
+            "summary" => Ok(WatcherEventKind::Summary),
+            "custom" => Ok(WatcherEventKind::Custom),
+            _ => Err(napi::Error::new(
+                Status::InvalidArg,
+                format!("UNKNOWN_EVENT_KIND. `{name}` is not a watcher event kind."),
+            )),
+        }
+    }
+}
+
+impl Display for WatcherEventKind {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            WatcherEvent::Close => write!(f, "close"),
-            WatcherEvent::Event(_) => write!(f, "event"),
-            WatcherEvent::ReStart => write!(f, "restart"),
+            WatcherEventKind::Close => write!(f, "close"),
+            WatcherEventKind::Event => write!(f, "event"),
+            WatcherEventKind::ReStart => write!(f, "restart"),
+            WatcherEventKind::Change => write!(f, "change"),
+            WatcherEventKind::ChangeBatch => write!(f, "change_batch"),
+            WatcherEventKind::Summary => write!(f, "summary"),
+            WatcherEventKind::Custom => write!(f, "custom"),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BundleEvent {
     Start,
     BundleStart,
-    End,
+    BundleEnd,
+    End(BundleEndEventData),
+    Error(Vec<BindingError>),
 }
 
 impl Display for BundleEvent {
@@ -51,32 +207,132 @@ impl Display for BundleEvent {
         match self {
             BundleEvent::Start => write!(f, "START"),
             BundleEvent::BundleStart => write!(f, "BUNDLE_START"),
-            BundleEvent::End => write!(f, "END"),
+            BundleEvent::BundleEnd => write!(f, "BUNDLE_END"),
+            BundleEvent::End(_) => write!(f, "END"),
+            BundleEvent::Error(_) => write!(f, "ERROR"),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BundleEndEventData {
+    /// The primary (entry) output.
     pub output: String,
     pub duration: u32,
+    /// Every file the bundle emitted, `output` included.
+    #[serde(default)]
+    pub outputs: Vec<OutputFile>,
 }
 
-#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OutputFile {
+    pub path: String,
+    /// In bytes.
+    pub size: u32,
+}
+
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum WatcherChangeKind {
     Create,
     Update,
     Delete,
+    /// The file at `old_path` was moved to `path`.
+    Rename,
+}
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct WatcherChangeData {
+    pub path: String,
+    pub kind: WatcherChangeKind,
+    /// Where the file was before a `Rename`, `None` for the other kinds.
+    #[serde(default)]
+    pub old_path: Option<String>,
 }
+
 #[napi]
 pub struct BindingWatcherChangeData {
     pub path: String,
-    pub kind: String,
+    kind: WatcherChangeKind,
+    /// Where the file was before a `rename`.
+    pub old_path: Option<String>,
+}
+
+#[napi]
+impl BindingWatcherChangeData {
+    pub fn kind(&self) -> WatcherChangeKind {
+        self.kind
+    }
+
+    /// `create`, `update`, `delete` or `rename`.
+    #[napi(getter, js_name = "kind")]
+    pub fn kind_name(&self) -> String {
+        self.kind.to_string()
+    }
+}
+
+impl From<&WatcherChangeData> for BindingWatcherChangeData {
+    fn from(change: &WatcherChangeData) -> Self {
+        Self {
+            path: change.path.clone(),
+            kind: change.kind,
+            old_path: change.old_path.clone(),
+        }
+    }
 }
 #[napi]
 pub struct BindingBundleEndEventData {
     pub output: String,
     pub duration: u32,
+    outputs: Vec<OutputFile>,
+}
+
+impl From<&BundleEndEventData> for BindingBundleEndEventData {
+    fn from(data: &BundleEndEventData) -> Self {
+        Self {
+            output: data.output.clone(),
+            duration: data.duration,
+            outputs: data.outputs.clone(),
+        }
+    }
+}
+
+#[napi]
+impl BindingBundleEndEventData {
+    /// Every file the bundle emitted, `output` included, e.g. to list the artifacts of a build.
+    #[napi]
+    pub fn outputs(&self) -> Vec<BindingOutputFile> {
+        self.outputs.iter().map(Into::into).collect()
+    }
+}
+
+#[napi(object)]
+pub struct BindingOutputFile {
+    pub path: String,
+    /// In bytes.
+    pub size: u32,
+}
+
+impl From<&OutputFile> for BindingOutputFile {
+    fn from(file: &OutputFile) -> Self {
+        Self {
+            path: file.path.clone(),
+            size: file.size,
+        }
+    }
+}
+
+impl WatcherChangeKind {
+    /// The net effect of a change of kind `self` followed by one of kind `next` to the same path,
+    /// `None` if they cancel out.
+    fn then(self, next: WatcherChangeKind) -> Option<WatcherChangeKind> {
+        use WatcherChangeKind::*;
+        match (self, next) {
+            (Create, Delete) => None,
+            (Create, _) => Some(Create),
+            (Delete, Create) => Some(Update),
+            (Rename, Create | Update) => Some(Rename),
+            (_, next) => Some(next),
+        }
+    }
 }
 
 impl Display for WatcherChangeKind {
@@ -85,24 +341,263 @@ impl Display for WatcherChangeKind {
             WatcherChangeKind::Create => write!(f, "create"),
             WatcherChangeKind::Update => write!(f, "update"),
             WatcherChangeKind::Delete => write!(f, "delete"),
+            WatcherChangeKind::Rename => write!(f, "rename"),
+        }
+    }
+}
+
+/// Parses what `Display` writes.
+impl std::str::FromStr for WatcherChangeKind {
+    type Err = napi::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "create" => Ok(WatcherChangeKind::Create),
+            "update" => Ok(WatcherChangeKind::Update),
+            "delete" => Ok(WatcherChangeKind::Delete),
+            "rename" => Ok(WatcherChangeKind::Rename),
+            name => Err(napi::Error::new(
+                Status::InvalidArg,
+                format!("UNKNOWN_CHANGE_KIND. `{name}` is not a change kind."),
+            )),
+        }
+    }
+}
+
+impl TryFrom<&str> for WatcherChangeKind {
+    type Error = napi::Error;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+bitflags::bitflags! {
+    /// A set of `WatcherChangeKind`s, written like `"create|delete"`.
+    #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+    pub struct WatcherChangeKindMask: u8 {
+        const CREATE = 1 << 0;
+        const UPDATE = 1 << 1;
+        const DELETE = 1 << 2;
+        const RENAME = 1 << 3;
+    }
+}
+
+impl From<WatcherChangeKind> for WatcherChangeKindMask {
+    fn from(kind: WatcherChangeKind) -> Self {
+        match kind {
+            WatcherChangeKind::Create => WatcherChangeKindMask::CREATE,
+            WatcherChangeKind::Update => WatcherChangeKindMask::UPDATE,
+            WatcherChangeKind::Delete => WatcherChangeKindMask::DELETE,
+            WatcherChangeKind::Rename => WatcherChangeKindMask::RENAME,
+        }
+    }
+}
+
+impl Display for WatcherChangeKindMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let names = [
+            (WatcherChangeKindMask::CREATE, "create"),
+            (WatcherChangeKindMask::UPDATE, "update"),
+            (WatcherChangeKindMask::DELETE, "delete"),
+            (WatcherChangeKindMask::RENAME, "rename"),
+        ];
+        let mut names = names
+            .iter()
+            .filter(|(bit, _)| self.contains(*bit))
+            .map(|(_, name)| *name);
+        if let Some(first) = names.next() {
+            f.write_str(first)?;
         }
+        for name in names {
+            write!(f, "|{name}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for WatcherChangeKindMask {
+    type Err = napi::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split('|')
+            .map(|name| name.trim().parse::<WatcherChangeKind>())
+            .try_fold(WatcherChangeKindMask::empty(), |mask, kind| {
+                Ok(mask | kind?.into())
+            })
     }
 }
 
 #[napi]
+#[derive(Clone, Debug)]
 pub struct BindingWatcherEvent {
     inner: WatcherEvent,
+    config: Option<String>,
+    seq: u32,
+    cause_seq: Option<u32>,
+    context: Option<String>,
 }
 #[napi]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BindingError {
     pub kind: String,
     pub message: String,
+    /// How many times this error occurred. Always `1` unless the watcher de-duplicates errors,
+    /// see `BindingWatcherOptions::dedupe_errors`.
+    #[serde(default = "BindingError::default_count")]
+    pub count: u32,
+    /// The stack trace of the JS error this error was created from, if any.
+    #[serde(default)]
+    pub stack: Option<String>,
+    /// A stable code identifying the error: the `code` of the JS error it was created from, or the
+    /// code our own error messages start with, e.g. `CALLBACK_TIMED_OUT`.
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+impl BindingError {
+    pub fn new(kind: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            message: message.into(),
+            count: 1,
+            stack: None,
+            code: None,
+        }
+    }
+
+    /// Like the `From<napi::Error>` conversion, plus the `stack` and `code` of the JS error `e`
+    /// was created from, e.g. the rejection of a listener, see `js_call::js_error_details`.
+    pub async fn capture(e: napi::Error) -> Self {
+        let details = js_call::js_error_details(&e).await.unwrap_or_default();
+        let mut error = Self::from(e);
+        error.stack = details.stack;
+        error.code = details.code.or(error.code);
+        error
+    }
+
+    fn default_count() -> u32 {
+        1
+    }
+}
+
+/// `kind` is the status and `message` the reason. The `code` is the one the reason starts with,
+/// as in `CODE. message`, if any. There is no `stack`, which takes reading the JS error and is
+/// what `BindingError::capture` is for.
+impl From<napi::Error> for BindingError {
+    fn from(e: napi::Error) -> Self {
+        let code = e
+            .reason
+            .split_once(". ")
+            .map(|(code, _)| code)
+            .filter(|code| {
+                !code.is_empty()
+                    && code
+                        .chars()
+                        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+            })
+            .map(str::to_owned);
+        Self {
+            code,
+            ..Self::new(e.status.as_ref(), e.reason)
+        }
+    }
+}
+
+/// An error about the watcher itself rather than about the bundle it drives. Its `Display` starts
+/// with a stable code, which is also what the converted `napi::Error`'s message starts with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BindingWatcherError {
+    StartFailed(String),
+    InvalidConfig(String),
+    /// `start` or `startBlocking` was called on a watcher that is already running.
+    AlreadyRunning,
+    /// There is no async runtime to drive the watcher on.
+    RuntimeMissing,
+    /// A custom event was emitted under a name that isn't registered, or with another payload type
+    /// than the one registered.
+    UnknownEventType(String),
+}
+
+impl BindingWatcherError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            BindingWatcherError::StartFailed(_) => "START_FAILED",
+            BindingWatcherError::InvalidConfig(_) => "INVALID_CONFIG",
+            BindingWatcherError::AlreadyRunning => "ALREADY_RUNNING",
+            BindingWatcherError::RuntimeMissing => "RUNTIME_MISSING",
+            BindingWatcherError::UnknownEventType(_) => "UNKNOWN_EVENT_TYPE",
+        }
+    }
+}
+
+impl Display for BindingWatcherError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let code = self.code();
+        match self {
+            BindingWatcherError::StartFailed(reason)
+            | BindingWatcherError::InvalidConfig(reason)
+            | BindingWatcherError::UnknownEventType(reason) => {
+                write!(f, "{code}. {reason}")
+            }
+            BindingWatcherError::AlreadyRunning => {
+                write!(f, "{code}. The watcher is already running.")
+            }
+            BindingWatcherError::RuntimeMissing => {
+                write!(
+                    f,
+                    "{code}. No async runtime is available to drive the watcher."
+                )
+            }
+        }
+    }
+}
+
+impl From<BindingWatcherError> for napi::Error {
+    fn from(e: BindingWatcherError) -> Self {
+        let status = match e {
+            BindingWatcherError::InvalidConfig(_) | BindingWatcherError::UnknownEventType(_) => {
+                Status::InvalidArg
+            }
+            _ => Status::GenericFailure,
+        };
+        napi::Error::new(status, e.to_string())
+    }
 }
 
+/// The `try_*` and other `Option`-returning accessors give Js `null` for `None`, never `undefined`,
+/// so `event.tryBundleEndData() === null` holds for a mismatched event.
 #[napi]
 impl BindingWatcherEvent {
     pub fn new(inner: WatcherEvent) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            config: None,
+            seq: 0,
+            cause_seq: None,
+            context: None,
+        }
+    }
+
+    pub fn with_cause_seq(mut self, cause_seq: Option<u32>) -> Self {
+        self.cause_seq = cause_seq;
+        self
+    }
+
+    pub fn with_context(mut self, context: Option<String>) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn with_seq(mut self, seq: u32) -> Self {
+        self.seq = seq;
+        self
+    }
+
+    /// Tag the event with the name of the watch config that produced it.
+    pub fn with_config(mut self, config: impl Into<String>) -> Self {
+        self.config = Some(config.into());
+        self
     }
 
     #[napi]
@@ -110,98 +605,2798 @@ impl BindingWatcherEvent {
         self.inner.to_string()
     }
 
+    /// Whether both events are of the same kind with equal payloads. Where they were emitted
+    /// (`config`, `seq`, `causeSeq`) is not compared.
+    #[napi]
+    pub fn equals(&self, other: &BindingWatcherEvent) -> bool {
+        self.inner == other.inner
+    }
+
+    /// Name of the watch config this event originates from, if any.
+    #[napi]
+    pub fn config(&self) -> Option<String> {
+        self.config.clone()
+    }
+
+    /// The app-level context the watcher carried when the event was emitted, see `setContext`.
+    #[napi]
+    pub fn context(&self) -> Option<String> {
+        self.context.clone()
+    }
+
+    /// Position of the event in the watcher's stream. It increases by one per emitted event and
+    /// resets to `0` on `start` and `restart`; the `restart` event itself carries the new base.
+    #[napi]
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    /// For a bundle `END`, the `seq` of the latest change of the same config that triggered the
+    /// rebuild. `None` when no such change was seen since the previous `END`.
+    #[napi]
+    pub fn cause_seq(&self) -> Option<u32> {
+        self.cause_seq
+    }
+
+    /// The error returned by the accessors below when called on the wrong kind of event.
+    fn kind_mismatch(&self, expected: &str) -> napi::Error {
+        napi::Error::new(
+            Status::InvalidArg,
+            format!(
+                "EVENT_KIND_MISMATCH. Expected a `{expected}` event, got `{}`.",
+                self.inner
+            ),
+        )
+    }
+
+    /// Throws for anything but a `change` event, so JS can catch the mismatch.
+    #[napi]
+    pub fn watch_change_data(&self) -> napi::Result<BindingWatcherChangeData> {
+        self.watch_change_data_result()
+    }
+
     #[napi]
-    pub fn watch_change_data(&self) -> BindingWatcherChangeData {
+    pub fn try_watch_change_data(&self) -> Option<BindingWatcherChangeData> {
+        self.watch_change_data_result().ok()
+    }
+
+    #[napi]
+    pub fn watch_change_data_result(&self) -> napi::Result<BindingWatcherChangeData> {
         match &self.inner {
-            _ => {
-                unreachable!("Expected WatcherEvent::Change")
-            }
+            WatcherEvent::Change(change) => Ok(change.into()),
+            _ => Err(self.kind_mismatch("change")),
         }
     }
 
+    /// The changes carried by a `change` or `change_batch` event, empty for every other event.
     #[napi]
-    pub fn bundle_end_data(&self) -> BindingBundleEndEventData {
+    pub fn changes(&self) -> Vec<BindingWatcherChangeData> {
         match &self.inner {
-            _ => {
-                unreachable!("Expected WatcherEvent::Event(BundleEventKind::BundleEnd)")
-            }
+            WatcherEvent::Change(change) => vec![change.into()],
+            WatcherEvent::ChangeBatch(changes) => changes.iter().map(Into::into).collect(),
+            _ => Vec::new(),
         }
     }
 
+    /// The output and duration of a finished build. Throws for anything but a bundle `END` event.
+    #[napi]
+    pub fn bundle_end_data(&self) -> napi::Result<BindingBundleEndEventData> {
+        self.bundle_end_data_result()
+    }
+
     #[napi]
-    pub fn bundle_event_kind(&self) -> String {
+    pub fn try_bundle_end_data(&self) -> Option<BindingBundleEndEventData> {
+        self.bundle_end_data_result().ok()
+    }
+
+    #[napi]
+    pub fn bundle_end_data_result(&self) -> napi::Result<BindingBundleEndEventData> {
         match &self.inner {
-            WatcherEvent::Event(kind) => kind.to_string(),
-            _ => {
-                unreachable!("Expected WatcherEvent::Event")
-            }
+            WatcherEvent::Event(BundleEvent::End(data)) => Ok(data.into()),
+            _ => Err(self.kind_mismatch("END")),
+        }
+    }
+
+    /// The kind of a bundle event, e.g. `BUNDLE_END`. Throws for anything but an `event` event.
+    #[napi]
+    pub fn bundle_event_kind(&self) -> napi::Result<String> {
+        match &self.inner {
+            WatcherEvent::Event(kind) => Ok(kind.to_string()),
+            _ => Err(self.kind_mismatch("event")),
+        }
+    }
+
+    #[napi]
+    pub fn try_bundle_event_kind(&self) -> Option<String> {
+        self.bundle_event_kind().ok()
+    }
+
+    /// The stats carried by a `summary` event. Throws for every other event.
+    #[napi]
+    pub fn summary(&self) -> napi::Result<BindingWatcherStats> {
+        match &self.inner {
+            WatcherEvent::Summary(stats) => Ok(stats.clone()),
+            _ => Err(self.kind_mismatch("summary")),
+        }
+    }
+
+    /// The name a `custom` event was emitted under. Throws for every other event.
+    #[napi]
+    pub fn custom_name(&self) -> napi::Result<String> {
+        match &self.inner {
+            WatcherEvent::Custom { name, .. } => Ok(name.clone()),
+            _ => Err(self.kind_mismatch("custom")),
+        }
+    }
+
+    /// The payload of a `custom` event, as the object its type serializes to. Throws for every
+    /// other event.
+    #[napi(ts_return_type = "any")]
+    pub fn custom_payload(&self) -> napi::Result<serde_json::Value> {
+        self.custom_payload_as()
+    }
+
+    /// `custom_payload` as the type it was emitted with, or any other type it deserializes to.
+    pub fn custom_payload_as<T: DeserializeOwned>(&self) -> napi::Result<T> {
+        match &self.inner {
+            WatcherEvent::Custom { name, payload } => serde_json::from_str(payload).map_err(|e| {
+                napi::Error::new(
+                    Status::InvalidArg,
+                    format!("INVALID_PAYLOAD. Couldn't deserialize the `{name}` payload: {e}."),
+                )
+            }),
+            _ => Err(self.kind_mismatch("custom")),
         }
     }
 
+    /// The errors carried by a bundle `ERROR` event, empty for every other event. Reading them
+    /// leaves the event untouched, so every call returns the same list.
     #[napi]
-    pub fn errors(&mut self) -> Vec<napi::Either<napi::JsError, BindingError>> {
-        unimplemented!("errors")
+    pub fn errors(&self) -> Vec<napi::Either<napi::JsError, BindingError>> {
+        match &self.inner {
+            WatcherEvent::Event(BundleEvent::Error(errors)) => {
+                errors.iter().cloned().map(napi::Either::B).collect()
+            }
+            _ => Vec::new(),
+        }
     }
 }
 
 // use tokio_with_wasm::alias as tokio;
 
-use tokio::task::{spawn, spawn_blocking, yield_now, JoinSet};
+use tokio::task::{spawn, JoinHandle, JoinSet};
 use tokio_with_wasm::alias as tokio;
 
-#[napi]
-pub struct BindingWatcher {}
+#[napi(module_exports)]
+pub fn init(_exports: Object, env: Env) -> napi::Result<()> {
+    js_call::track_env_teardown(&env)?;
+    js_call::track_js_thread(&env)
+}
 
-#[napi]
-impl BindingWatcher {
-    #[napi(constructor)]
-    pub fn new() -> napi::Result<Self> {
-        Ok(Self {})
+/// `spawn` that keeps the spawned work inside the caller's `tracing` span, so logs emitted by the
+/// task stay correlated with e.g. the `#[tracing::instrument]` span of the method spawning it.
+fn spawn_in_current_span<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    spawn(future.instrument(tracing::Span::current()))
+}
+
+pub type WatcherListener = MaybeAsyncJsCallback<BindingWatcherEvent, ()>;
+
+/// A listener receiving each burst of events as one array, see `BindingWatcher::emit_batch`.
+pub type WatcherBatchListener = MaybeAsyncJsCallback<Vec<BindingWatcherEvent>, ()>;
+
+/// A listener as received from JS. Converting it checks that the value is callable, so passing
+/// e.g. a plain object fails right at registration with `LISTENER_NOT_CALLABLE` instead of
+/// cryptically on the first call.
+pub struct JsListener<Args: 'static + JsValuesTupleIntoVec = BindingWatcherEvent>(
+    pub MaybeAsyncJsCallback<Args, ()>,
+);
+
+impl<Args: 'static + JsValuesTupleIntoVec> TypeName for JsListener<Args> {
+    fn type_name() -> &'static str {
+        "Function"
     }
 
-    #[tracing::instrument(level = "debug", skip_all)]
-    #[napi(ts_args_type = "listener: (data: BindingWatcherEvent) => void")]
-    pub async fn start(&self, listener: MaybeAsyncJsCallback<(), ()>) -> napi::Result<()> {
-        let f = async move {
-            println!("why here is not running");
+    fn value_type() -> ValueType {
+        ValueType::Function
+    }
+}
 
-            println!("async call");
-            if let Err(e) = listener.await_call(()).await {
-                println!("async watcher listener error: {:?}", e);
-                eprintln!("async watcher listener error: {e:?}");
-            }
-            println!("async no lock");
-        };
+impl<Args: 'static + JsValuesTupleIntoVec> FromNapiValue for JsListener<Args> {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> napi::Result<Self> {
+        let value_type = Unknown::from_napi_value(env, napi_val)?.get_type()?;
+        if value_type != ValueType::Function {
+            return Err(napi::Error::new(
+                Status::InvalidArg,
+                format!("LISTENER_NOT_CALLABLE. Expected the listener to be a function, got {value_type}."),
+            ));
+        }
+        #[cfg(feature = "metrics")]
+        return ffi_latency::timed_listener(env, napi_val).map(Self);
+        #[cfg(not(feature = "metrics"))]
+        MaybeAsyncJsCallback::<Args, ()>::from_napi_value(env, napi_val).map(Self)
+    }
+}
 
-        spawn(f);
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct BindingWatcherOptions {
+    pub paths: Vec<String>,
+    /// Globs of the paths whose changes are ignored while watching, e.g. to skip `node_modules`.
+    /// They're matched against absolute paths, without `.` or `..` components.
+    pub ignore: Option<Vec<String>>,
+    /// Whether to watch `paths` recursively. Defaults to `true`.
+    pub recursive: Option<bool>,
+    /// Collect the changes arriving within this many milliseconds into a single batch event.
+    pub batch_window_ms: Option<u32>,
+    /// Collapse the changes to the same path arriving within this many milliseconds of its first
+    /// one into their net effect, e.g. a `create` then `update` into a `create`. A `create` then
+    /// `delete` cancels out and emits nothing. A `delete` and a `create` of the same file (by
+    /// inode) are reported as a single `rename`. Applies before `batch_window_ms`.
+    pub debounce_ms: Option<u32>,
+    /// Maximum number of events forwarded per second, by event kind (as returned by
+    /// `eventKind()`). Excess events are dropped and counted in `stats().droppedEvents`. Kinds
+    /// without an entry are unlimited.
+    pub rate_limits: Option<HashMap<String, u32>>,
+    /// Report each distinct error (same kind and message) once per bundle cycle. Every `ERROR`
+    /// event then carries all the distinct errors seen since the config's latest `BUNDLE_START`,
+    /// each with the number of times it occurred in `count`. Defaults to `false`.
+    pub dedupe_errors: Option<bool>,
+    /// Cancel the bundle running in `emitJoinSet` when a change to the same config arrives, and
+    /// emit a `BUNDLE_START` for the fresh bundle the change calls for. Defaults to `false`.
+    pub cancel_in_flight_on_change: Option<bool>,
+    /// Emit a `summary` event carrying `stats()` right before the final `close` event. Defaults
+    /// to `false`.
+    pub summary_on_close: Option<bool>,
+    /// Cap on the total time spent awaiting listeners for a single event (or burst, for batch
+    /// listeners). Once it's used up, the listeners still to be awaited are abandoned and logged.
+    pub emit_budget_ms: Option<u32>,
+    /// How the events waiting to be picked up through `poll` are ordered: `fifo` (the default)
+    /// keeps emit order, `priority` hands out bundle `ERROR` and `END` events ahead of the other
+    /// events already waiting. With `priority`, an error can thus be seen before changes emitted
+    /// earlier. Listeners are called as each event is emitted, so their order never changes.
+    pub ordering: Option<String>,
+    /// Log each event at `info` level instead of calling the listeners, e.g. to check which
+    /// events get produced regardless of what the listeners do. Everything else, such as `stats()`
+    /// and `poll`, works as usual. Defaults to `false`.
+    pub dry_run: Option<bool>,
+    /// Deliver an event again to a listener that threw or rejected on it, up to this many times,
+    /// before its error is handled as usual. The next event is only delivered to the listener
+    /// once it has handled this one, so it sees each event until it succeeds. Defaults to `0`.
+    pub max_redeliveries: Option<u32>,
+    /// How many of the latest events are kept on record for `replayTo` and `recentEvents`. Once
+    /// that many are, each new event overwrites the oldest. Lowering it drops the oldest events
+    /// with the next emit, and `0` keeps none. Defaults to `128`.
+    pub history_capacity: Option<u32>,
+}
 
-        napi::tokio::task::spawn(async move {
-            println!("why here is not running1");
-        })
-        .await;
-        Ok(())
+#[napi(object)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BindingWatcherStats {
+    /// Events emitted, not counting those dropped by the rate limiter.
+    pub total_events: u32,
+    /// Bundle `ERROR` events among `totalEvents`.
+    pub errors: u32,
+    /// Milliseconds since the latest `start`, or since the watcher was created if never started.
+    pub session_duration_ms: u32,
+    /// Events dropped by the rate limiter.
+    pub dropped_events: u32,
+    /// Listener calls started and not settled yet.
+    pub pending_calls: u32,
+    /// The highest `pendingCalls` seen since the latest `start`, for capacity planning.
+    pub peak_queue_depth: u32,
+    /// Average milliseconds a listener call waited between being queued and entering JS, over
+    /// every watcher's calls so far. Only measured with the `metrics` feature, and unset before
+    /// the first call.
+    pub avg_ffi_latency_ms: Option<f64>,
+}
+
+// The average is never NaN.
+impl Eq for BindingWatcherStats {}
+
+impl Hash for BindingWatcherStats {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.total_events.hash(state);
+        self.errors.hash(state);
+        self.session_duration_ms.hash(state);
+        self.dropped_events.hash(state);
+        self.pending_calls.hash(state);
+        self.peak_queue_depth.hash(state);
+        self.avg_ffi_latency_ms.map(f64::to_bits).hash(state);
     }
+}
 
-    #[tracing::instrument(level = "debug", skip_all)]
-    #[napi(ts_args_type = "listener: (data: BindingWatcherEvent) => void")]
-    pub async fn loop_spawn(&self, listener: MaybeAsyncJsCallback<(), ()>) -> napi::Result<()> {
-        let f = async move {
-            println!("why here is not running");
-            println!("async call");
-            if let Err(e) = listener.await_call(()).await {
-                println!("async watcher listener error: {:?}", e);
-                eprintln!("async watcher listener error: {e:?}");
-            }
-            println!("async no lock");
-        };
+/// Reject `options` that would only fail once the watcher starts.
+fn check_options(options: &BindingWatcherOptions) -> napi::Result<()> {
+    if let Some(ignore) = &options.ignore {
+        fs_watch::compile_globs(ignore).map_err(|reason| {
+            BindingWatcherError::InvalidConfig(format!("Invalid `ignore` glob: {reason}."))
+        })?;
+    }
+    if let Some(ordering) = &options.ordering {
+        EventOrdering::from_name(ordering)?;
+    }
+    Ok(())
+}
 
-        spawn(f);
+/// What to do with an error raised by a listener, as decided by the watcher's error handler.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorAction {
+    /// Drop the error and carry on emitting.
+    Swallow,
+    /// Surface the error to the caller of `emit`.
+    Rethrow,
+    /// Invoke the listener again with the same event.
+    Retry,
+}
 
-        napi::tokio::task::spawn(async move {
-            println!("why here is not running1");
-            loop {}
-        })
-        .await;
-        Ok(())
+pub type ErrorHandler = Box<dyn Fn(napi::Error) -> ErrorAction + Send + Sync>;
+
+/// Transforms an event on the Rust side before it is handed to the listener.
+pub type ArgMiddleware = Box<dyn Fn(WatcherEvent) -> WatcherEvent + Send + Sync>;
+
+/// Upper bound on `ErrorAction::Retry` for a single event, so a handler that always retries cannot
+/// spin forever on a listener that always fails.
+const MAX_LISTENER_RETRIES: u32 = 3;
+
+/// Number of recent events kept for `replay_to` and `recent_events` unless
+/// `BindingWatcherOptions::history_capacity` says otherwise.
+const HISTORY_CAPACITY: usize = 128;
+
+/// How many recent events `options` ask to keep: once that many are on record, each new one
+/// overwrites the oldest.
+fn history_capacity(options: &BindingWatcherOptions) -> usize {
+    options
+        .history_capacity
+        .map_or(HISTORY_CAPACITY, |capacity| capacity as usize)
+}
+
+/// How long the first half of a rename reported in halves is held for the second half, see
+/// `BindingWatcher::emit_fs_event`.
+const RENAME_PAIR_WINDOW: Duration = Duration::from_millis(50);
+
+/// A named watch configuration. One `BindingWatcher` can drive several of them, and every event
+/// it emits is tagged with the name of the config it belongs to.
+#[derive(Clone, Debug)]
+struct WatchConfig {
+    name: String,
+    options: BindingWatcherOptions,
+}
+
+/// See `BindingWatcherOptions::ordering`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum EventOrdering {
+    #[default]
+    Fifo,
+    Priority,
+}
+
+impl EventOrdering {
+    fn from_name(name: &str) -> napi::Result<Self> {
+        match name {
+            "fifo" => Ok(EventOrdering::Fifo),
+            "priority" => Ok(EventOrdering::Priority),
+            _ => Err(BindingWatcherError::InvalidConfig(format!(
+                "Unknown `ordering` `{name}`, expected `fifo` or `priority`."
+            ))
+            .into()),
+        }
+    }
+
+    /// The ordering `options` ask for. Options are checked when set, so an unknown name can't
+    /// show up here.
+    fn of(options: &BindingWatcherOptions) -> Self {
+        options
+            .ordering
+            .as_deref()
+            .and_then(|name| Self::from_name(name).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Events waiting to be picked up through `BindingWatcher::poll` or `next_events`.
+#[derive(Default)]
+struct PollQueue {
+    /// Set by the first `poll`, so nothing piles up for embedders that never poll.
+    enabled: bool,
+    events: VecDeque<BindingWatcherEvent>,
+    /// One per pending `poll`, as `poll` and `next_events` may wait at the same time.
+    wakers: Vec<Waker>,
+}
+
+impl PollQueue {
+    fn push(&mut self, event: &BindingWatcherEvent, ordering: EventOrdering) {
+        if !self.enabled {
+            return;
+        }
+        if ordering == EventOrdering::Priority && Self::is_urgent(&event.inner) {
+            // Behind the urgent events already waiting, ahead of everything else.
+            let at = self
+                .events
+                .iter()
+                .position(|queued| !Self::is_urgent(&queued.inner))
+                .unwrap_or(self.events.len());
+            self.events.insert(at, event.clone());
+        } else {
+            self.events.push_back(event.clone());
+        }
+        self.wake();
+    }
+
+    /// Whether `event` jumps the queue under `EventOrdering::Priority`.
+    fn is_urgent(event: &WatcherEvent) -> bool {
+        matches!(
+            event,
+            WatcherEvent::Event(BundleEvent::Error(_) | BundleEvent::End(_))
+        )
+    }
+
+    fn wake(&mut self) {
+        for waker in self.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// A changed path, with the config it belongs to (`None` for the watcher-wide paths).
+type ChangedPath = (Option<String>, String);
+
+/// The inodes `fs_watch::file_ids` takes in the background, keyed like `WatcherInner::file_ids`.
+type FileIdsWalk = JoinHandle<Vec<(ChangedPath, u64)>>;
+
+/// The net change to a path waiting for its debounce window to elapse.
+struct DebouncedChange {
+    kind: WatcherChangeKind,
+    /// Where the file was before it was renamed to the path.
+    old_path: Option<String>,
+    /// The inode of the file that was deleted, so a file created with it within the window can be
+    /// reported as the same file renamed.
+    file_id: Option<u64>,
+}
+
+impl DebouncedChange {
+    /// The net change of `self` followed by `next`, see `WatcherChangeKind::then`.
+    fn then(self, next: DebouncedChange) -> Option<DebouncedChange> {
+        let kind = self.kind.then(next.kind)?;
+        let old_path = match (self.kind, kind) {
+            (_, WatcherChangeKind::Rename) => next.old_path.or(self.old_path),
+            // Renamed to the path then deleted, which settles as the old path being deleted.
+            (WatcherChangeKind::Rename, WatcherChangeKind::Delete) => self.old_path,
+            _ => None,
+        };
+        Some(DebouncedChange {
+            kind,
+            old_path,
+            file_id: next.file_id,
+        })
+    }
+
+    fn settle(self, path: String) -> WatcherChangeData {
+        match (self.kind, self.old_path) {
+            (WatcherChangeKind::Delete, Some(old_path)) => WatcherChangeData {
+                path: old_path,
+                kind: WatcherChangeKind::Delete,
+                old_path: None,
+            },
+            (kind, old_path) => WatcherChangeData {
+                path,
+                kind,
+                old_path,
+            },
+        }
+    }
+}
+
+/// State shared between a `BindingWatcher` and the tasks it spawns.
+struct WatcherInner {
+    core: WatcherCore,
+    configs: Mutex<Vec<WatchConfig>>,
+    /// Listeners for every kind of event. Their ids are shared with `kind_listeners`.
+    listeners: Emitter<BindingWatcherEvent, ()>,
+    /// Listeners subscribed to some kinds only, indexed by kind so an emit only visits the
+    /// listeners interested in it. A listener subscribed to several kinds is in each bucket.
+    kind_listeners: Mutex<HashMap<WatcherEventKind, BTreeMap<u32, WatcherListener>>>,
+    /// Listeners for the changes of some kinds only, with the kinds they're subscribed to.
+    change_listeners: Mutex<BTreeMap<u32, (WatcherChangeKindMask, WatcherListener)>>,
+    /// Listeners for the events none of `kind_listeners` and `change_listeners` matched.
+    fallback_listeners: Mutex<BTreeMap<u32, WatcherListener>>,
+    /// Ids of the listeners skipped by emits until resumed, see `pauseListener`.
+    paused_listeners: Mutex<HashSet<u32>>,
+    batch_listeners: Emitter<Vec<BindingWatcherEvent>, ()>,
+    error_handler: Mutex<Option<Arc<ErrorHandler>>>,
+    /// Changes waiting for their config's batch window to elapse, see `batch_window_ms`.
+    pending_changes: Mutex<HashMap<Option<String>, Vec<WatcherChangeData>>>,
+    /// Net change per config and path waiting for its debounce window to elapse, `None` once the
+    /// changes cancelled out. See `debounce_ms`.
+    debounced_changes: Mutex<HashMap<ChangedPath, Option<DebouncedChange>>>,
+    /// The inode of each path there when watching started or last seen created or updated while
+    /// debouncing, to match a delete of it with a create of the same file. See
+    /// `DebouncedChange::file_id`.
+    file_ids: Mutex<HashMap<ChangedPath, u64>>,
+    /// The walk taking the inodes of the files there when watching started, off the runtime's
+    /// threads. Merged into `file_ids` by the first debounced change after it, see
+    /// `await_file_ids_walk`.
+    file_ids_walk: Mutex<Option<FileIdsWalk>>,
+    /// The old path of each rename whose first half arrived and whose second is awaited, by
+    /// config and the tracker `notify` pairs the halves with.
+    held_renames: Mutex<HashMap<(Option<String>, usize), String>>,
+    /// Cancels the bundle of each config `emit_join_set` is draining.
+    bundles: Mutex<HashMap<String, CancellationToken>>,
+    /// Set by the first `start` or `startBlocking`.
+    running: AtomicBool,
+    /// Set by the first `close`.
+    closing: AtomicBool,
+    /// Cancelled by `close` once the `close` event is delivered, so the emits still in progress
+    /// stop awaiting their listeners. Every listener call is made cancellable by it.
+    abandon: CancellationToken,
+    /// Number of `dispatch_all` calls in progress. `close` only tears the watcher down once the
+    /// last of them is done.
+    active_emits: Mutex<u32>,
+    /// Background work spawned by the watcher, aborted on `close`.
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+    /// The payload type of each custom event name, see `register_event_type`.
+    event_types: Mutex<HashMap<String, EventType>>,
+}
+
+/// The payload type a custom event name was registered with.
+struct EventType {
+    id: TypeId,
+    name: Cow<'static, str>,
+}
+
+impl Deref for WatcherInner {
+    type Target = WatcherCore;
+
+    fn deref(&self) -> &WatcherCore {
+        &self.core
+    }
+}
+
+impl WatcherInner {
+    /// `spawn_in_current_span`, keeping the task's handle so `close` can abort it.
+    fn spawn_task(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(spawn_in_current_span(task));
+    }
+
+    fn config_options(&self, name: &str) -> Option<BindingWatcherOptions> {
+        let configs = self.configs.lock().unwrap();
+        configs
+            .iter()
+            .find(|config| config.name == name)
+            .map(|config| config.options.clone())
+    }
+
+    fn check_config(&self, config: &str) -> napi::Result<BindingWatcherOptions> {
+        self.config_options(config).ok_or_else(|| {
+            BindingWatcherError::InvalidConfig(format!(
+                "Watch config `{config}` is not registered."
+            ))
+            .into()
+        })
+    }
+
+    /// The options applying to the changes of `config`, the watcher-wide ones for `None`.
+    fn change_options(&self, config: Option<&str>) -> napi::Result<BindingWatcherOptions> {
+        match config {
+            Some(config) => self.check_config(config),
+            None => Ok(self.options.lock().unwrap().clone()),
+        }
+    }
+
+    /// Run `event` through the middlewares, number it and fan it out to every listener. `config`
+    /// is `None` for events that belong to the watcher as a whole rather than to one config.
+    async fn dispatch(&self, config: Option<&str>, event: WatcherEvent) -> napi::Result<()> {
+        self.dispatch_all(config, vec![event]).await
+    }
+
+    /// Like `dispatch` for each of `events`, except that batch listeners get the whole burst in a
+    /// single call.
+    async fn dispatch_all(
+        &self,
+        config: Option<&str>,
+        events: Vec<WatcherEvent>,
+    ) -> napi::Result<()> {
+        if self.abandon.is_cancelled() {
+            return Ok(());
+        }
+        let _active = self.begin_emit();
+        let events: Vec<_> = events
+            .into_iter()
+            .filter_map(|event| self.process(config, event))
+            .collect();
+        if events.is_empty() {
+            return Ok(());
+        }
+        if self.options.lock().unwrap().dry_run.unwrap_or(false) {
+            if !is_quiet() {
+                for event in &events {
+                    tracing::info!(
+                        "dry run, not delivering event {} of config {:?}: {:?}",
+                        event.seq,
+                        event.config,
+                        event.inner
+                    );
+                }
+            }
+            return Ok(());
+        }
+        let budget = self
+            .options
+            .lock()
+            .unwrap()
+            .emit_budget_ms
+            .map(|ms| Duration::from_millis(ms.into()));
+        // Work on snapshots so listeners can register or unregister others mid-emit.
+        let listeners = self.listeners.listeners();
+        for event in &events {
+            let deadline = budget.map(|budget| self.clock.now() + budget);
+            for (id, listener) in self.listeners_for(&event.inner, &listeners) {
+                if self.abandon.is_cancelled() {
+                    return Ok(());
+                }
+                if self.paused_listeners.lock().unwrap().contains(&id) {
+                    continue;
+                }
+                self.deliver_until(id, &listener, event, deadline).await?;
+            }
+        }
+        let deadline = budget.map(|budget| self.clock.now() + budget);
+        for (id, listener) in self.batch_listeners.listeners() {
+            if self.abandon.is_cancelled() {
+                return Ok(());
+            }
+            self.deliver_until(id, &listener, &events, deadline).await?;
+        }
+        Ok(())
+    }
+
+    /// Count an emit as in progress until the returned guard is dropped.
+    fn begin_emit(&self) -> ActiveEmit<'_> {
+        *self.active_emits.lock().unwrap() += 1;
+        ActiveEmit(self)
+    }
+
+    /// Stop everything the watcher runs in the background. Doing it again does nothing.
+    fn tear_down(&self) {
+        self.cancel.cancel();
+        error_throttle::flush();
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+        self.poll_queue.lock().unwrap().wake();
+    }
+
+    /// `deliver`, abandoning the listener if it hasn't settled by `deadline`. An abandoned listener
+    /// is logged and counts as delivered.
+    async fn deliver_until<Args>(
+        &self,
+        id: u32,
+        listener: &MaybeAsyncJsCallback<Args, ()>,
+        args: &Args,
+        deadline: Option<Instant>,
+    ) -> napi::Result<()>
+    where
+        Args: Clone + Send + 'static + JsValuesTupleIntoVec,
+    {
+        let Some(deadline) = deadline else {
+            return self.deliver(listener, args).await;
+        };
+        let remaining = deadline.saturating_duration_since(self.clock.now());
+        if !remaining.is_zero() {
+            let delivery = pin!(self.deliver(listener, args));
+            if let Raced::Left((result, _)) =
+                future::select(delivery, self.clock.sleep(remaining)).await
+            {
+                return result;
+            }
+        }
+        if !is_quiet() {
+            tracing::warn!("emit budget exceeded, abandoned listener {id}");
+        }
+        Ok(())
+    }
+
+    /// The catch-all `listeners` plus those subscribed to the kind of `event` or, for changes, to
+    /// the kind of change, in registration order. When no subscribed listener matches, the
+    /// fallback listeners take their place.
+    fn listeners_for(
+        &self,
+        event: &WatcherEvent,
+        listeners: &[(u32, WatcherListener)],
+    ) -> Vec<(u32, WatcherListener)> {
+        match_listeners(
+            event,
+            listeners,
+            &self.kind_listeners.lock().unwrap(),
+            &self.change_listeners.lock().unwrap(),
+            &self.fallback_listeners.lock().unwrap(),
+        )
+    }
+
+    /// Whether a listener sharing the `listeners` id space is registered under `id`.
+    fn has_listener(&self, id: u32) -> bool {
+        self.listeners.contains(id)
+            || self.change_listeners.lock().unwrap().contains_key(&id)
+            || self.fallback_listeners.lock().unwrap().contains_key(&id)
+            || self
+                .kind_listeners
+                .lock()
+                .unwrap()
+                .values()
+                .any(|bucket| bucket.contains_key(&id))
+    }
+
+    async fn deliver<Args>(
+        &self,
+        listener: &MaybeAsyncJsCallback<Args, ()>,
+        args: &Args,
+    ) -> napi::Result<()>
+    where
+        Args: Clone + Send + 'static + JsValuesTupleIntoVec,
+    {
+        let _pending = self.track_call();
+        let max_redeliveries = self.options.lock().unwrap().max_redeliveries.unwrap_or(0);
+        let mut redeliveries = 0;
+        let mut retries = 0;
+        loop {
+            // Closing stops the wait even mid-way through a listener's promise, e.g. one awaiting
+            // `close` itself. The Js side keeps running, but whatever it resolves to is ignored.
+            let call = listener.await_call_cancellable(args.clone(), self.abandon.clone());
+            let e = match call.await {
+                Ok(()) => return Ok(()),
+                Err(e) if e.status == Status::Cancelled && self.abandon.is_cancelled() => {
+                    return Ok(())
+                }
+                Err(e) => e,
+            };
+            if redeliveries < max_redeliveries {
+                redeliveries += 1;
+                diag!(
+                    "redelivering to a failed listener ({redeliveries}/{max_redeliveries}): {e:?}"
+                );
+                continue;
+            }
+            let handler = self.error_handler.lock().unwrap().clone();
+            let action = match (handler, e.try_clone()) {
+                (Some(handler), Ok(copy)) => handler(copy),
+                _ => ErrorAction::Rethrow,
+            };
+            match action {
+                ErrorAction::Swallow => return Ok(()),
+                ErrorAction::Retry if retries < MAX_LISTENER_RETRIES => retries += 1,
+                ErrorAction::Retry | ErrorAction::Rethrow => return Err(e),
+            }
+        }
+    }
+
+    /// Wait for the walk `watch_paths` started, if it's still pending, and merge the inodes it
+    /// took into `file_ids`. Those seen since take precedence. A file deleted before the walk got
+    /// to it has no inode to pair its delete with.
+    async fn await_file_ids_walk(&self) {
+        let walk = self.file_ids_walk.lock().unwrap().take();
+        let Some(walk) = walk else {
+            return;
+        };
+        match walk.await {
+            Ok(ids) => {
+                let mut file_ids = self.file_ids.lock().unwrap();
+                for (key, id) in ids {
+                    file_ids.entry(key).or_insert(id);
+                }
+            }
+            Err(e) => diag_err!("failed to take the inodes of the watched files: {e:?}"),
+        }
+    }
+
+    /// Emit everything queued for `config` as a single `ChangeBatch` event.
+    async fn flush_changes(&self, config: Option<&str>) -> napi::Result<()> {
+        let changes = self
+            .pending_changes
+            .lock()
+            .unwrap()
+            .remove(&config.map(str::to_string));
+        match changes {
+            Some(changes) if !changes.is_empty() => {
+                self.dispatch(config, WatcherEvent::ChangeBatch(changes))
+                    .await
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The listeners of [`WatcherInner::listeners_for`], picked from its registries.
+fn match_listeners<L: Clone>(
+    event: &WatcherEvent,
+    listeners: &[(u32, L)],
+    kind_listeners: &HashMap<WatcherEventKind, BTreeMap<u32, L>>,
+    change_listeners: &BTreeMap<u32, (WatcherChangeKindMask, L)>,
+    fallback_listeners: &BTreeMap<u32, L>,
+) -> Vec<(u32, L)> {
+    let mut merged = listeners.to_vec();
+    if let Some(bucket) = kind_listeners.get(&event.kind()) {
+        merged.extend(bucket.iter().map(|(id, listener)| (*id, listener.clone())));
+    }
+    let changed = match event {
+        WatcherEvent::Change(change) => change.kind.into(),
+        WatcherEvent::ChangeBatch(changes) => changes
+            .iter()
+            .fold(WatcherChangeKindMask::empty(), |mask, change| {
+                mask | change.kind.into()
+            }),
+        _ => WatcherChangeKindMask::empty(),
+    };
+    if !changed.is_empty() {
+        merged.extend(
+            change_listeners
+                .iter()
+                .filter(|(_, (mask, _))| mask.intersects(changed))
+                .map(|(id, (_, listener))| (*id, listener.clone())),
+        );
+    }
+    if merged.len() == listeners.len() {
+        merged.extend(
+            fallback_listeners
+                .iter()
+                .map(|(id, listener)| (*id, listener.clone())),
+        );
+    }
+    if merged.len() > listeners.len() {
+        merged.sort_by_key(|(id, _)| *id);
+    }
+    merged
+}
+
+/// Counts an emit as in progress until dropped. The last one to finish after `close` tears the
+/// watcher down.
+struct ActiveEmit<'a>(&'a WatcherInner);
+
+impl Drop for ActiveEmit<'_> {
+    fn drop(&mut self) {
+        let mut active_emits = self.0.active_emits.lock().unwrap();
+        *active_emits -= 1;
+        if *active_emits == 0 && self.0.abandon.is_cancelled() {
+            drop(active_emits);
+            self.0.tear_down();
+        }
+    }
+}
+
+/// Cloning gives another handle to the same watcher, as does `share` on the JS side: listeners,
+/// options, stats and the closed state are all shared between the handles.
+#[napi]
+#[derive(Clone)]
+pub struct BindingWatcher {
+    inner: Arc<WatcherInner>,
+}
+
+impl BindingWatcher {
+    /// A watcher whose batch windows and rate limits run on `clock` instead of tokio time.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            inner: Arc::new(WatcherInner {
+                core: WatcherCore::new(clock),
+                configs: Mutex::new(Vec::new()),
+                listeners: Emitter::new(),
+                kind_listeners: Mutex::new(HashMap::new()),
+                change_listeners: Mutex::new(BTreeMap::new()),
+                fallback_listeners: Mutex::new(BTreeMap::new()),
+                paused_listeners: Mutex::new(HashSet::new()),
+                batch_listeners: Emitter::new(),
+                error_handler: Mutex::new(None),
+                pending_changes: Mutex::new(HashMap::new()),
+                debounced_changes: Mutex::new(HashMap::new()),
+                file_ids: Mutex::new(HashMap::new()),
+                file_ids_walk: Mutex::new(None),
+                held_renames: Mutex::new(HashMap::new()),
+                bundles: Mutex::new(HashMap::new()),
+                running: AtomicBool::new(false),
+                closing: AtomicBool::new(false),
+                abandon: CancellationToken::new(),
+                active_emits: Mutex::new(0),
+                tasks: Mutex::new(Vec::new()),
+                event_types: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Start watching the watcher-wide paths and those of every config registered so far.
+    fn watch_paths(
+        &self,
+    ) -> napi::Result<(Vec<notify::RecommendedWatcher>, UnboundedReceiver<FsEvent>)> {
+        let defaults = self.inner.options.lock().unwrap().clone();
+        let source = |config: Option<String>, options: &BindingWatcherOptions| WatchSource {
+            config,
+            paths: options.paths.clone(),
+            recursive: options.recursive.or(defaults.recursive).unwrap_or(true),
+            ignore: options
+                .ignore
+                .clone()
+                .or_else(|| defaults.ignore.clone())
+                .unwrap_or_default(),
+        };
+        let mut sources = vec![source(None, &defaults)];
+        sources.extend(
+            self.inner
+                .configs
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|config| source(Some(config.name.clone()), &config.options)),
+        );
+        // Debouncing pairs a delete and a create of the same file by inode, which the deleted file
+        // no longer has, so take those of the files already there.
+        let debounced: Vec<_> = sources
+            .iter()
+            .filter(|source| {
+                self.inner
+                    .change_options(source.config.as_deref())
+                    .ok()
+                    .and_then(|options| options.debounce_ms.or(defaults.debounce_ms))
+                    .is_some_and(|ms| ms > 0)
+            })
+            .cloned()
+            .collect();
+        let watching = fs_watch::watch_all(sources).map_err(|reason| {
+            BindingWatcherError::StartFailed(format!(
+                "Couldn't watch the configured paths: {}.",
+                reason.trim_end_matches('.')
+            ))
+        })?;
+        if !debounced.is_empty() {
+            let walk = tokio::task::spawn_blocking(move || {
+                debounced
+                    .into_iter()
+                    .flat_map(|source| {
+                        fs_watch::file_ids(&source)
+                            .into_iter()
+                            .map(move |(path, id)| ((source.config.clone(), path), id))
+                    })
+                    .collect()
+            });
+            *self.inner.file_ids_walk.lock().unwrap() = Some(walk);
+        }
+        Ok(watching)
+    }
+
+    /// Emit what `fs_events` reports until it ends or the watcher is closed.
+    async fn forward_fs_events(&self, mut fs_events: UnboundedReceiver<FsEvent>) {
+        let cancel = self.inner.cancel.clone();
+        while let Some(Some((config, result))) = cancel.run_until_cancelled(fs_events.next()).await
+        {
+            let emitted = self.emit_fs_event(config.as_deref(), result);
+            if let Err(e) = self.catching_panics(config.as_deref(), emitted).await {
+                diag_err!("failed to emit filesystem event: {e:?}");
+            }
+        }
+    }
+
+    /// Await `step`, turning a panic into an `ERROR` event carrying a `panic` error, so that one
+    /// bad event doesn't bring down the watcher's task, or the process with it.
+    async fn catching_panics(
+        &self,
+        config: Option<&str>,
+        step: impl Future<Output = napi::Result<()>>,
+    ) -> napi::Result<()> {
+        let panic = match AssertUnwindSafe(step).catch_unwind().await {
+            Ok(result) => return result,
+            Err(panic) => panic,
+        };
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<dyn Any>".to_string());
+        diag_err!("watcher task panicked: {message}");
+        let error = BindingError::new("panic", message);
+        self.inner
+            .dispatch(config, WatcherEvent::Event(BundleEvent::Error(vec![error])))
+            .await
+    }
+
+    /// A change event per changed path, going through the debounce and batch windows if any, or
+    /// an `ERROR` event if watching failed.
+    async fn emit_fs_event(
+        &self,
+        config: Option<&str>,
+        result: notify::Result<notify::Event>,
+    ) -> napi::Result<()> {
+        let event = match result {
+            Ok(event) => event,
+            Err(e) => {
+                let error = BindingError::new("WatchError", e.to_string());
+                return self
+                    .inner
+                    .dispatch(config, WatcherEvent::Event(BundleEvent::Error(vec![error])))
+                    .await;
+            }
+        };
+        let Ok(mut kind) = WatcherChangeKind::try_from(event.kind) else {
+            return Ok(());
+        };
+        let renamed = matches!(
+            event.kind,
+            notify::EventKind::Modify(notify::event::ModifyKind::Name(
+                notify::event::RenameMode::From
+            ))
+        );
+        if let (true, Some(tracker), [old_path]) = (renamed, event.tracker(), &event.paths[..]) {
+            self.hold_rename(config, tracker, old_path.to_string_lossy().into_owned());
+            return Ok(());
+        }
+        if kind == WatcherChangeKind::Rename {
+            if let [old_path, path] = &event.paths[..] {
+                if let Some(tracker) = event.tracker() {
+                    let key = (config.map(str::to_string), tracker);
+                    self.inner.held_renames.lock().unwrap().remove(&key);
+                }
+                let change = WatcherChangeData {
+                    path: path.to_string_lossy().into_owned(),
+                    kind,
+                    old_path: Some(old_path.to_string_lossy().into_owned()),
+                };
+                return self.queue_change(config, change).await;
+            }
+            kind = WatcherChangeKind::Update;
+        }
+        // A backend reporting each path of a rename without saying which it is (FSEvents, kqueue)
+        // either removed the file at it or added one, which debouncing can pair.
+        let unpaired_rename = matches!(
+            event.kind,
+            notify::EventKind::Modify(notify::event::ModifyKind::Name(
+                notify::event::RenameMode::Any
+            ))
+        );
+        for path in event.paths {
+            let kind = if !unpaired_rename {
+                kind
+            } else if path.exists() {
+                WatcherChangeKind::Create
+            } else {
+                WatcherChangeKind::Delete
+            };
+            let change = WatcherChangeData {
+                path: path.to_string_lossy().into_owned(),
+                kind,
+                old_path: None,
+            };
+            self.queue_change(config, change).await?;
+        }
+        Ok(())
+    }
+
+    /// Hold the first half of a rename, which `notify` reports before the event carrying both
+    /// paths, so the rename isn't also reported as a delete. If no such event follows within
+    /// `RENAME_PAIR_WINDOW`, e.g. as the file moved out of the watched paths, it's a delete.
+    fn hold_rename(&self, config: Option<&str>, tracker: usize, old_path: String) {
+        let key = (config.map(str::to_string), tracker);
+        self.inner
+            .held_renames
+            .lock()
+            .unwrap()
+            .insert(key.clone(), old_path);
+        let watcher = self.clone();
+        self.inner.spawn_task(async move {
+            watcher.inner.clock.sleep(RENAME_PAIR_WINDOW).await;
+            let held = watcher.inner.held_renames.lock().unwrap().remove(&key);
+            let (config, _) = key;
+            if let Some(path) = held {
+                let change = WatcherChangeData {
+                    path,
+                    kind: WatcherChangeKind::Delete,
+                    old_path: None,
+                };
+                if let Err(e) = watcher.queue_change(config.as_deref(), change).await {
+                    diag_err!("failed to emit the delete of a moved file: {e:?}");
+                }
+            }
+        });
+    }
+}
+
+#[napi]
+impl BindingWatcher {
+    /// `options` are the watcher-wide options, see `reconfigure`.
+    #[napi(constructor)]
+    pub fn new(options: Option<BindingWatcherOptions>) -> napi::Result<Self> {
+        let watcher = Self::with_clock(Arc::new(TokioClock));
+        if let Some(options) = options {
+            watcher.reconfigure(options)?;
+        }
+        Ok(watcher)
+    }
+
+    /// Another handle to this watcher, e.g. to hand to code that shouldn't hold the original.
+    /// Both drive the same watcher, so closing either closes it for both.
+    #[napi]
+    pub fn share(&self) -> Self {
+        self.clone()
+    }
+
+    /// A snapshot of the watcher-wide options, as last set by `reconfigure`.
+    #[napi]
+    pub fn options(&self) -> BindingWatcherOptions {
+        self.inner.options.lock().unwrap().clone()
+    }
+
+    /// Replace the watcher-wide options. Takes effect for the events that follow. Fails with
+    /// `INVALID_CONFIG` if an `ignore` glob doesn't parse or the `ordering` is unknown.
+    #[napi]
+    pub fn reconfigure(&self, opts: BindingWatcherOptions) -> napi::Result<()> {
+        check_options(&opts)?;
+        *self.inner.options.lock().unwrap() = opts;
+        Ok(())
+    }
+
+    /// Attach `ctx` (e.g. a build id) to every event emitted from now on, including in the event
+    /// log. Replaces any previous context.
+    #[napi]
+    pub fn set_context(&self, ctx: String) {
+        *self.inner.context.lock().unwrap() = Some(ctx);
+    }
+
+    /// Counters describing what the watcher did with the events it was given.
+    #[napi]
+    pub fn stats(&self) -> BindingWatcherStats {
+        self.inner.stats()
+    }
+
+    /// Register a named watch config. Names must be unique within a watcher.
+    #[napi]
+    pub fn add_config(&self, name: String, opts: BindingWatcherOptions) -> napi::Result<()> {
+        check_options(&opts)?;
+        let mut configs = self.inner.configs.lock().unwrap();
+        if configs.iter().any(|config| config.name == name) {
+            return Err(BindingWatcherError::InvalidConfig(format!(
+                "Watch config `{name}` is already registered."
+            ))
+            .into());
+        }
+        configs.push(WatchConfig {
+            name,
+            options: opts,
+        });
+        Ok(())
+    }
+
+    #[napi]
+    pub fn config_names(&self) -> Vec<String> {
+        let configs = self.inner.configs.lock().unwrap();
+        configs.iter().map(|config| config.name.clone()).collect()
+    }
+
+    pub fn config_options(&self, name: &str) -> Option<BindingWatcherOptions> {
+        self.inner.config_options(name)
+    }
+
+    /// Register another listener and return the id to remove it with. Listeners added while an
+    /// event is being emitted only receive the events that follow.
+    #[napi(ts_args_type = "listener: (data: BindingWatcherEvent) => void")]
+    pub fn add_listener(&self, listener: JsListener) -> u32 {
+        self.inner.listeners.add_listener(listener.0)
+    }
+
+    /// Returns `false` if no listener is registered under `id`. A listener removed while an event
+    /// is being emitted still receives that event.
+    #[napi]
+    pub fn remove_listener(&self, id: u32) -> bool {
+        let mut removed = self.inner.listeners.remove_listener(id);
+        removed |= self
+            .inner
+            .change_listeners
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .is_some();
+        removed |= self
+            .inner
+            .fallback_listeners
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .is_some();
+        for bucket in self.inner.kind_listeners.lock().unwrap().values_mut() {
+            removed |= bucket.remove(&id).is_some();
+        }
+        self.inner.paused_listeners.lock().unwrap().remove(&id);
+        removed
+    }
+
+    /// Skip the listener registered under `id` in every emit until `resumeListener`, while the
+    /// other listeners carry on. The events emitted meanwhile are not delivered to it later.
+    /// Returns `false` if no listener is registered under `id`. Batch listeners can't be paused.
+    #[napi]
+    pub fn pause_listener(&self, id: u32) -> bool {
+        if !self.inner.has_listener(id) {
+            return false;
+        }
+        self.inner.paused_listeners.lock().unwrap().insert(id);
+        true
+    }
+
+    /// Returns `false` if the listener registered under `id` wasn't paused.
+    #[napi]
+    pub fn resume_listener(&self, id: u32) -> bool {
+        self.inner.paused_listeners.lock().unwrap().remove(&id)
+    }
+
+    /// Like `addListener`, but the listener is only called for events whose `eventKind()` is one
+    /// of `kinds`. Remove it with `removeListener`.
+    #[napi(ts_args_type = "kinds: Array<string>, listener: (data: BindingWatcherEvent) => void")]
+    pub fn add_listener_for(&self, kinds: Vec<String>, listener: JsListener) -> napi::Result<u32> {
+        let kinds = kinds
+            .iter()
+            .map(|name| WatcherEventKind::from_name(name))
+            .collect::<napi::Result<Vec<_>>>()?;
+        let id = self.inner.listeners.reserve_id();
+        let mut kind_listeners = self.inner.kind_listeners.lock().unwrap();
+        for kind in kinds {
+            kind_listeners
+                .entry(kind)
+                .or_default()
+                .insert(id, listener.0.clone());
+        }
+        Ok(id)
+    }
+
+    /// Feed the recorded events to `listener` in order, for a late subscriber such as a devtools
+    /// panel. Only the last `max_replay` events are replayed when given, and at most the latest
+    /// `history_capacity` are on record. With `subscribe`, the listener is then registered for
+    /// live events and its id is returned; events emitted during the replay itself are not
+    /// delivered to it.
+    #[napi(
+        ts_args_type = "listener: (data: BindingWatcherEvent) => void, maxReplay?: number, subscribe?: boolean"
+    )]
+    pub async fn replay_to(
+        &self,
+        listener: JsListener,
+        max_replay: Option<u32>,
+        subscribe: Option<bool>,
+    ) -> napi::Result<Option<u32>> {
+        let history: Vec<_> = {
+            let history = self.inner.history.lock().unwrap();
+            let skip = max_replay.map_or(0, |max| history.len().saturating_sub(max as usize));
+            history.iter().skip(skip).cloned().collect()
+        };
+        for event in &history {
+            self.inner.deliver(&listener.0, event).await?;
+        }
+        Ok(subscribe
+            .unwrap_or(false)
+            .then(|| self.inner.listeners.add_listener(listener.0)))
+    }
+
+    /// The latest `limit` events on record, oldest first, or all of them without a `limit`. There
+    /// are never more than `history_capacity`.
+    #[napi]
+    pub fn recent_events(&self, limit: Option<u32>) -> Vec<BindingWatcherEvent> {
+        let history = self.inner.history.lock().unwrap();
+        let skip = limit.map_or(0, |limit| history.len().saturating_sub(limit as usize));
+        history.iter().skip(skip).cloned().collect()
+    }
+
+    /// Register a listener for the changes whose kind is in `mask`, e.g. `"create|delete"`. A
+    /// `change_batch` is delivered whole when any of its changes matches. Remove it with
+    /// `removeListener`.
+    #[napi(ts_args_type = "mask: string, listener: (data: BindingWatcherEvent) => void")]
+    pub fn add_change_listener(&self, mask: String, listener: JsListener) -> napi::Result<u32> {
+        let mask: WatcherChangeKindMask = mask.parse()?;
+        let id = self.inner.listeners.reserve_id();
+        self.inner
+            .change_listeners
+            .lock()
+            .unwrap()
+            .insert(id, (mask, listener.0));
+        Ok(id)
+    }
+
+    /// Register a listener for the events no `addListenerFor` or `addChangeListener` listener
+    /// matched, like the default case of a `switch`. Listeners added with `addListener` see every
+    /// event and don't count as a match. Remove it with `removeListener`.
+    #[napi(ts_args_type = "listener: (data: BindingWatcherEvent) => void")]
+    pub fn add_fallback_listener(&self, listener: JsListener) -> u32 {
+        let id = self.inner.listeners.reserve_id();
+        self.inner
+            .fallback_listeners
+            .lock()
+            .unwrap()
+            .insert(id, listener.0);
+        id
+    }
+
+    /// Register a listener called once per burst with all of its events, whatever their kinds;
+    /// tell them apart with `eventKind()`. A single emitted event arrives as a one-element array.
+    /// Batch listener ids are separate from `addListener` ids.
+    #[napi(ts_args_type = "listener: (events: Array<BindingWatcherEvent>) => void")]
+    pub fn add_batch_listener(&self, listener: JsListener<Vec<BindingWatcherEvent>>) -> u32 {
+        self.inner.batch_listeners.add_listener(listener.0)
+    }
+
+    #[napi]
+    pub fn remove_batch_listener(&self, id: u32) -> bool {
+        self.inner.batch_listeners.remove_listener(id)
+    }
+
+    /// Install a handler consulted whenever a listener errors. Without one, errors are rethrown.
+    pub fn set_error_handler(&self, handler: ErrorHandler) {
+        *self.inner.error_handler.lock().unwrap() = Some(Arc::new(handler));
+    }
+
+    /// Append a middleware to the chain every event goes through before reaching the listener.
+    /// Middlewares run in registration order.
+    pub fn add_arg_middleware(&self, middleware: ArgMiddleware) {
+        self.inner
+            .arg_middlewares
+            .lock()
+            .unwrap()
+            .push(Arc::new(middleware));
+    }
+
+    /// Record every subsequently emitted event into `log`, replacing any previous log.
+    pub fn set_event_log(&self, log: EventLog) {
+        *self.inner.event_log.lock().unwrap() = Some(log);
+    }
+
+    /// Also forward every subsequently emitted event to `transport`, e.g. `transport::stdio()` in
+    /// a worker process.
+    pub fn add_transport(&self, transport: Transport) {
+        self.inner.transports.lock().unwrap().push(transport);
+    }
+
+    /// Deliver `event` to every listener, tagged with the watch config it was produced by.
+    pub async fn emit(&self, config: &str, event: WatcherEvent) -> napi::Result<()> {
+        self.inner.check_config(config)?;
+        self.inner.dispatch(Some(config), event).await
+    }
+
+    /// Register a custom event type for `emit_typed`, e.g. for a plugin to report its own events.
+    /// Names must be unique within a watcher.
+    pub fn register_event_type<T>(&self, name: &str) -> napi::Result<()>
+    where
+        T: Serialize + DeserializeOwned + 'static,
+    {
+        let mut event_types = self.inner.event_types.lock().unwrap();
+        if event_types.contains_key(name) {
+            return Err(BindingWatcherError::InvalidConfig(format!(
+                "Custom event type `{name}` is already registered."
+            ))
+            .into());
+        }
+        let event_type = EventType {
+            id: TypeId::of::<T>(),
+            name: js_call::pretty_type_name::<T>(),
+        };
+        event_types.insert(name.to_string(), event_type);
+        Ok(())
+    }
+
+    /// Deliver a `custom` event carrying `payload`, which listeners get as the object it
+    /// serializes to through `customPayload()`. Fails with `UNKNOWN_EVENT_TYPE` unless `name` was
+    /// registered with `T` by `register_event_type`.
+    pub async fn emit_typed<T>(&self, name: &str, payload: T) -> napi::Result<()>
+    where
+        T: Serialize + 'static,
+    {
+        {
+            let event_types = self.inner.event_types.lock().unwrap();
+            let Some(event_type) = event_types.get(name) else {
+                return Err(BindingWatcherError::UnknownEventType(format!(
+                    "Custom event type `{name}` is not registered."
+                ))
+                .into());
+            };
+            if event_type.id != TypeId::of::<T>() {
+                return Err(BindingWatcherError::UnknownEventType(format!(
+                    "Custom event type `{name}` carries `{}`, not `{}`.",
+                    event_type.name,
+                    js_call::pretty_type_name::<T>()
+                ))
+                .into());
+            }
+        }
+        let payload = serde_json::to_string(&payload).map_err(|e| {
+            napi::Error::new(
+                Status::InvalidArg,
+                format!("INVALID_PAYLOAD. Couldn't serialize the `{name}` payload: {e}."),
+            )
+        })?;
+        let event = WatcherEvent::Custom {
+            name: name.to_string(),
+            payload,
+        };
+        self.inner.dispatch(None, event).await
+    }
+
+    /// Deliver a burst of events of any mix of kinds. Regular listeners still get one call per
+    /// event; batch listeners get the whole burst as one array, in order.
+    pub async fn emit_batch(&self, config: &str, events: Vec<WatcherEvent>) -> napi::Result<()> {
+        self.inner.check_config(config)?;
+        self.inner.dispatch_all(Some(config), events).await
+    }
+
+    /// Report a file change for `config`. With a `debounce_ms` set on the config or the watcher,
+    /// the changes to a path within the window are first collapsed into their net change, so an
+    /// editor saving a file in several writes is reported once.
+    ///
+    /// With a `batch_window_ms` set, changes are then queued and everything that arrives within
+    /// the window is emitted as one `ChangeBatch` event, which keeps mass changes (e.g. a
+    /// `git checkout`) from flooding JS with one call per file. Without it, each change is emitted
+    /// right away as a `Change` event.
+    pub async fn emit_change(&self, config: &str, change: WatcherChangeData) -> napi::Result<()> {
+        self.inner.check_config(config)?;
+        self.queue_change(Some(config), change).await
+    }
+
+    /// `emit_change` for a change to the paths of `config`, or to the watcher-wide paths when
+    /// it's `None`.
+    async fn queue_change(
+        &self,
+        config: Option<&str>,
+        change: WatcherChangeData,
+    ) -> napi::Result<()> {
+        let options = self.inner.change_options(config)?;
+        let debounce_ms = options
+            .debounce_ms
+            .or(self.inner.options.lock().unwrap().debounce_ms);
+        let Some(window) = debounce_ms.filter(|ms| *ms > 0) else {
+            return self.emit_settled_change(config, change).await;
+        };
+
+        self.inner.await_file_ids_walk().await;
+        let key = (config.map(str::to_string), change.path);
+        let opens_window = {
+            let mut debounced = self.inner.debounced_changes.lock().unwrap();
+            let mut file_ids = self.inner.file_ids.lock().unwrap();
+            let mut change = DebouncedChange {
+                kind: change.kind,
+                old_path: change.old_path,
+                file_id: None,
+            };
+            match change.kind {
+                WatcherChangeKind::Create | WatcherChangeKind::Update => {
+                    if let Some(id) = fs_watch::file_id(&key.1) {
+                        file_ids.insert(key.clone(), id);
+                        // A rename reported as a delete and a create, e.g. by a backend that
+                        // doesn't pair them.
+                        if change.kind == WatcherChangeKind::Create {
+                            change.old_path = debounced.iter().find_map(|(other, pending)| {
+                                let pending = pending.as_ref()?;
+                                (other.0 == key.0
+                                    && pending.kind == WatcherChangeKind::Delete
+                                    && pending.file_id == Some(id))
+                                .then(|| other.1.clone())
+                            });
+                            if change.old_path.is_some() {
+                                change.kind = WatcherChangeKind::Rename;
+                            }
+                        }
+                    }
+                }
+                WatcherChangeKind::Delete => change.file_id = file_ids.remove(&key),
+                WatcherChangeKind::Rename => {}
+            }
+            if let (WatcherChangeKind::Rename, Some(old_path)) = (change.kind, &change.old_path) {
+                let old_key = (key.0.clone(), old_path.clone());
+                if let Some(id) = file_ids.remove(&old_key) {
+                    file_ids.insert(key.clone(), id);
+                }
+                // The rename takes over what's pending for the old path. Its window is left open
+                // with nothing to settle, like changes that cancel out.
+                match debounced.get_mut(&old_key).and_then(Option::take) {
+                    Some(DebouncedChange {
+                        kind: WatcherChangeKind::Create,
+                        ..
+                    }) => {
+                        change.kind = WatcherChangeKind::Create;
+                        change.old_path = None;
+                    }
+                    Some(DebouncedChange {
+                        kind: WatcherChangeKind::Rename,
+                        old_path: Some(first),
+                        ..
+                    }) => change.old_path = Some(first),
+                    _ => {}
+                }
+            }
+            match debounced.get_mut(&key) {
+                Some(pending) => {
+                    *pending = match pending.take() {
+                        Some(pending) => pending.then(change),
+                        None => Some(change),
+                    };
+                    false
+                }
+                None => {
+                    debounced.insert(key.clone(), Some(change));
+                    true
+                }
+            }
+        };
+        if opens_window {
+            let watcher = BindingWatcher {
+                inner: Arc::clone(&self.inner),
+            };
+            self.inner.spawn_task(async move {
+                watcher
+                    .inner
+                    .clock
+                    .sleep(Duration::from_millis(window.into()))
+                    .await;
+                let settled = watcher.inner.debounced_changes.lock().unwrap().remove(&key);
+                let (config, path) = key;
+                if let Some(Some(pending)) = settled {
+                    let change = pending.settle(path);
+                    if let Err(e) = watcher.emit_settled_change(config.as_deref(), change).await {
+                        diag_err!("failed to emit debounced change: {e:?}");
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// The part of `emit_change` after debouncing.
+    async fn emit_settled_change(
+        &self,
+        config: Option<&str>,
+        change: WatcherChangeData,
+    ) -> napi::Result<()> {
+        let options = self.inner.change_options(config)?;
+        let cancel_in_flight = options
+            .cancel_in_flight_on_change
+            .or(self
+                .inner
+                .options
+                .lock()
+                .unwrap()
+                .cancel_in_flight_on_change)
+            .unwrap_or(false);
+        if let Some(config) = config.filter(|_| cancel_in_flight) {
+            let bundle = self.inner.bundles.lock().unwrap().remove(config);
+            if let Some(bundle) = bundle {
+                bundle.cancel();
+                self.inner
+                    .dispatch(Some(config), WatcherEvent::Event(BundleEvent::BundleStart))
+                    .await?;
+            }
+        }
+        let batch_window_ms =
+            options
+                .batch_window_ms
+                .or(self.inner.options.lock().unwrap().batch_window_ms);
+        let Some(window) = batch_window_ms.filter(|ms| *ms > 0) else {
+            return self
+                .inner
+                .dispatch(config, WatcherEvent::Change(change))
+                .await;
+        };
+
+        let opens_window = {
+            let mut pending = self.inner.pending_changes.lock().unwrap();
+            let queue = pending.entry(config.map(str::to_string)).or_default();
+            queue.push(change);
+            queue.len() == 1
+        };
+        if opens_window {
+            let inner = Arc::clone(&self.inner);
+            let config = config.map(str::to_string);
+            self.inner.spawn_task(async move {
+                inner
+                    .clock
+                    .sleep(Duration::from_millis(window.into()))
+                    .await;
+                if let Err(e) = inner.flush_changes(config.as_deref()).await {
+                    diag_err!("failed to emit change batch: {e:?}");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Drain `tasks` as they complete, turning each result into an event: a `BUNDLE_END` for a task
+    /// that succeeded and an `ERROR` for one that failed or panicked. A failing task doesn't abort
+    /// the others. Once every task is done, a final `END` is emitted, carrying how long draining
+    /// took and no `output`, as the tasks don't report one.
+    ///
+    /// With `cancel_in_flight_on_change` set, a change to `config` meanwhile aborts the remaining
+    /// tasks and fails with `CANCELLED` instead.
+    pub async fn emit_join_set(
+        &self,
+        config: &str,
+        mut tasks: JoinSet<napi::Result<()>>,
+    ) -> napi::Result<()> {
+        let started = self.inner.clock.now();
+        let bundle = CancellationToken::new();
+        self.inner
+            .bundles
+            .lock()
+            .unwrap()
+            .insert(config.to_string(), bundle.clone());
+        let drained = self.drain_bundle(config, &mut tasks, &bundle).await;
+        if !bundle.is_cancelled() {
+            self.inner.bundles.lock().unwrap().remove(config);
+        }
+        drained?;
+        let duration = self.inner.clock.now().duration_since(started);
+        let data = BundleEndEventData {
+            output: String::new(),
+            duration: duration.as_millis().try_into().unwrap_or(u32::MAX),
+            outputs: Vec::new(),
+        };
+        self.emit(config, WatcherEvent::Event(BundleEvent::End(data)))
+            .await
+    }
+
+    /// The part of `emit_join_set` emitting an event per finished task, until `bundle` is
+    /// cancelled.
+    async fn drain_bundle(
+        &self,
+        config: &str,
+        tasks: &mut JoinSet<napi::Result<()>>,
+        bundle: &CancellationToken,
+    ) -> napi::Result<()> {
+        loop {
+            let Some(next) = bundle.run_until_cancelled(tasks.join_next()).await else {
+                tasks.abort_all();
+                return Err(napi::Error::new(
+                    Status::Cancelled,
+                    "CANCELLED. The bundle was superseded by a newer change.",
+                ));
+            };
+            let Some(result) = next else {
+                return Ok(());
+            };
+            let event = match result {
+                Ok(Ok(())) => BundleEvent::BundleEnd,
+                Ok(Err(e)) => BundleEvent::Error(vec![BindingError::capture(e).await]),
+                Err(e) => BundleEvent::Error(vec![BindingError::new("JoinError", e.to_string())]),
+            };
+            self.emit(config, WatcherEvent::Event(event)).await?;
+        }
+    }
+
+    /// Stop the watcher: deliver a final `close` event, then abandon the listener calls still
+    /// pending and abort the watcher's background tasks. Nothing is emitted afterwards. Closing an
+    /// already closed watcher does nothing.
+    ///
+    /// A listener may await `close` too: the emit that called it stops awaiting its listeners,
+    /// and the teardown waits until that emit is done.
+    #[napi]
+    pub async fn close(&self) -> napi::Result<()> {
+        if self.inner.closing.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let summary_on_close = self.inner.options.lock().unwrap().summary_on_close;
+        let mut events = Vec::new();
+        if summary_on_close.unwrap_or(false) {
+            events.push(WatcherEvent::Summary(self.inner.stats()));
+        }
+        events.push(WatcherEvent::Close);
+        let delivered = self.inner.dispatch_all(None, events).await;
+        self.inner.abandon.cancel();
+        if *self.inner.active_emits.lock().unwrap() == 0 {
+            self.inner.tear_down();
+        }
+        self.inner.cancel.cancelled().await;
+        delivered
+    }
+
+    /// Pull the next emitted event without going through a JS listener, from any executor.
+    ///
+    /// Events are buffered from the first call on, so call it until it returns `Ready(None)`,
+    /// which happens once the watcher is closed and every buffered event has been handed out.
+    pub fn poll(&self, cx: &mut Context<'_>) -> Poll<Option<WatcherEvent>> {
+        self.poll_event(cx)
+            .map(|event| event.map(|event| event.inner))
+    }
+
+    /// `poll`, keeping what the event was tagged with when emitted, such as its `seq`.
+    fn poll_event(&self, cx: &mut Context<'_>) -> Poll<Option<BindingWatcherEvent>> {
+        let mut queue = self.inner.poll_queue.lock().unwrap();
+        queue.enabled = true;
+        if let Some(event) = queue.events.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        if self.inner.is_closed() {
+            return Poll::Ready(None);
+        }
+        if !queue.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            queue.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    /// Resolve with the next `count` events emitted, in order, or with those emitted until the
+    /// watcher is closed if fewer. Events are buffered from the first call to this or to `poll`
+    /// on, and each is handed out to one of them only.
+    #[napi(ts_return_type = "Promise<Array<BindingWatcherEvent>>")]
+    pub fn next_events<'env>(
+        &self,
+        env: &'env Env,
+        count: u32,
+    ) -> napi::Result<PromiseRaw<'env, Vec<BindingWatcherEvent>>> {
+        // Right away rather than once the returned promise's future first runs, so the events
+        // emitted right after the call are caught too.
+        self.inner.poll_queue.lock().unwrap().enabled = true;
+        let watcher = self.clone();
+        env.spawn_future(async move {
+            let mut events = Vec::new();
+            while events.len() < count as usize {
+                match future::poll_fn(|cx| watcher.poll_event(cx)).await {
+                    Some(event) => events.push(event),
+                    None => break,
+                }
+            }
+            Ok(events)
+        })
+    }
+
+    /// [`BindingWatcher::poll`] as a `Stream`.
+    pub fn events(&self) -> impl Stream<Item = WatcherEvent> + '_ {
+        futures::stream::poll_fn(move |cx| self.poll(cx))
+    }
+
+    #[napi]
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Reset the sequence counter and emit a `restart` event carrying the new base (`0`), so
+    /// consumers tracking `seq` know the following gap-free numbering starts over.
+    #[napi]
+    pub async fn restart(&self) -> napi::Result<()> {
+        self.inner.seq.store(0, Ordering::Relaxed);
+        self.inner.dispatch(None, WatcherEvent::ReStart).await
+    }
+
+    /// Like `start`, but drives the watcher on the calling task instead of a spawned one, which
+    /// suits single-threaded runtimes and simple scripts. The returned promise only settles once
+    /// the watcher is closed.
+    #[tracing::instrument(level = "debug", skip_all)]
+    #[napi(ts_args_type = "listener: (data: BindingWatcherEvent) => void")]
+    pub async fn start_blocking(&self, listener: JsListener) -> napi::Result<()> {
+        if self.inner.running.swap(true, Ordering::AcqRel) {
+            return Err(BindingWatcherError::AlreadyRunning.into());
+        }
+        let (_watchers, fs_events) = self.watch_paths().inspect_err(|_| {
+            self.inner.running.store(false, Ordering::Release);
+        })?;
+        self.inner.seq.store(0, Ordering::Relaxed);
+        self.inner.reset_session();
+        self.inner.listeners.add_listener(listener.0);
+        match self
+            .inner
+            .dispatch(None, WatcherEvent::Event(BundleEvent::Start))
+            .await
+        {
+            // Closing is how a blocking start is meant to end.
+            Err(e) if e.status != Status::Cancelled => return Err(e),
+            _ => {}
+        }
+        self.forward_fs_events(fs_events).await;
+        self.inner.cancel.cancelled().await;
+        Ok(())
+    }
+
+    /// Register `listener` and start watching the watcher-wide `paths` and those of every config
+    /// added so far, recursively. File changes are emitted as `change` events (or batches, see
+    /// `batchWindowMs`) and watching failures as bundle `ERROR` events, until the watcher is
+    /// closed.
+    ///
+    /// Resolves once every path is watched, so changes made right after
+    /// `await watcher.start(listener)` are not missed, and fails with `START_FAILED` if a path
+    /// can't be watched.
+    ///
+    /// A panic while handling an event is reported as an `ERROR` event carrying an error of kind
+    /// `panic`, and the watcher carries on with the next event.
+    #[tracing::instrument(level = "debug", skip_all)]
+    #[napi(ts_args_type = "listener: (data: BindingWatcherEvent) => void")]
+    pub async fn start(&self, listener: JsListener) -> napi::Result<()> {
+        self.start_with(Some(listener.0))
+    }
+
+    /// `start`, registering `listener` once the paths are watched.
+    fn start_with(&self, listener: Option<WatcherListener>) -> napi::Result<()> {
+        if self.inner.running.swap(true, Ordering::AcqRel) {
+            return Err(BindingWatcherError::AlreadyRunning.into());
+        }
+        // The backends are armed by the time this returns, and queue what they report until
+        // it's forwarded below.
+        let (watchers, fs_events) = self.watch_paths().inspect_err(|_| {
+            self.inner.running.store(false, Ordering::Release);
+        })?;
+        self.inner.seq.store(0, Ordering::Relaxed);
+        self.inner.reset_session();
+        if let Some(listener) = listener {
+            self.inner.listeners.add_listener(listener);
+        }
+        let watcher = BindingWatcher {
+            inner: Arc::clone(&self.inner),
+        };
+        self.inner.spawn_task(async move {
+            let started = watcher
+                .inner
+                .dispatch(None, WatcherEvent::Event(BundleEvent::Start));
+            if let Err(e) = watcher.catching_panics(None, started).await {
+                diag_err!("watcher listener error: {e:?}");
+            }
+            watcher.forward_fs_events(fs_events).await;
+            drop(watchers);
+        });
+        Ok(())
+    }
+
+    /// Call `listener` once in the background, then wait until the watcher is closed.
+    #[tracing::instrument(level = "debug", skip_all)]
+    #[napi(ts_args_type = "listener: (data: BindingWatcherEvent) => void")]
+    pub async fn loop_spawn(&self, listener: MaybeAsyncJsCallback<(), ()>) -> napi::Result<()> {
+        let watcher = self.clone();
+        let f = async move {
+            diag!("calling the loop_spawn listener");
+            let called = listener.await_call(());
+            if let Err(e) = watcher.catching_panics(None, called).await {
+                diag_err!("async watcher listener error: {e:?}");
+            }
+        };
+
+        self.inner.spawn_task(f);
+
+        self.inner.cancel.cancelled().await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::event::{CreateKind, ModifyKind, RemoveKind, RenameMode};
+
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn watcher(clock: &Arc<MockClock>, options: BindingWatcherOptions) -> BindingWatcher {
+        let watcher = BindingWatcher::with_clock(Arc::clone(clock) as Arc<dyn Clock>);
+        watcher.reconfigure(options).unwrap();
+        watcher
+    }
+
+    async fn run_tasks() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Let the spawned tasks start sleeping, move `clock` forward and let those it woke run.
+    async fn advance(clock: &MockClock, by: Duration) {
+        run_tasks().await;
+        clock.advance(by);
+        run_tasks().await;
+    }
+
+    /// `(kind, old_path, path)` of every change emitted so far.
+    fn changes(watcher: &BindingWatcher) -> Vec<(String, Option<String>, String)> {
+        watcher
+            .recent_events(None)
+            .iter()
+            .flat_map(BindingWatcherEvent::changes)
+            .map(|change| (change.kind_name(), change.old_path, change.path))
+            .collect()
+    }
+
+    fn fs_event(kind: notify::EventKind, paths: &[&str]) -> notify::Event {
+        paths.iter().fold(notify::Event::new(kind), |event, path| {
+            event.add_path(path.into())
+        })
+    }
+
+    fn rename(mode: RenameMode) -> notify::EventKind {
+        notify::EventKind::Modify(ModifyKind::Name(mode))
+    }
+
+    #[tokio::test]
+    async fn rename_reported_in_halves_is_one_rename() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(&clock, BindingWatcherOptions::default());
+        let from = fs_event(rename(RenameMode::From), &["/w/a"]).set_tracker(1);
+        let both = fs_event(rename(RenameMode::Both), &["/w/a", "/w/b"]).set_tracker(1);
+        watcher.emit_fs_event(None, Ok(from)).await.unwrap();
+        watcher.emit_fs_event(None, Ok(both)).await.unwrap();
+        advance(&clock, RENAME_PAIR_WINDOW * 2).await;
+        assert_eq!(
+            changes(&watcher),
+            [(
+                "rename".to_string(),
+                Some("/w/a".to_string()),
+                "/w/b".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn unpaired_first_half_of_rename_is_a_delete() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(&clock, BindingWatcherOptions::default());
+        let from = fs_event(rename(RenameMode::From), &["/w/a"]).set_tracker(1);
+        watcher.emit_fs_event(None, Ok(from)).await.unwrap();
+        assert_eq!(changes(&watcher), []);
+        advance(&clock, RENAME_PAIR_WINDOW * 2).await;
+        assert_eq!(
+            changes(&watcher),
+            [("delete".to_string(), None, "/w/a".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn rename_without_both_paths_is_an_update() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(&clock, BindingWatcherOptions::default());
+        let both = fs_event(rename(RenameMode::Both), &["/w/a"]);
+        watcher.emit_fs_event(None, Ok(both)).await.unwrap();
+        assert_eq!(
+            changes(&watcher),
+            [("update".to_string(), None, "/w/a".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_and_create_of_a_file_there_before_watching_is_a_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs_watch::normalize(dir.path()).unwrap();
+        let [old, new] = ["a", "b"].map(|name| dir.join(name).to_string_lossy().into_owned());
+        std::fs::write(&old, "x").unwrap();
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(
+            &clock,
+            BindingWatcherOptions {
+                paths: vec![dir.to_string_lossy().into_owned()],
+                debounce_ms: Some(100),
+                ..Default::default()
+            },
+        );
+        let _watching = watcher.watch_paths().unwrap();
+        watcher.inner.await_file_ids_walk().await;
+        std::fs::rename(&old, &new).unwrap();
+        // What a backend that doesn't pair the halves of a rename reports.
+        let delete = fs_event(notify::EventKind::Remove(RemoveKind::File), &[&old]);
+        let create = fs_event(notify::EventKind::Create(CreateKind::File), &[&new]);
+        watcher.emit_fs_event(None, Ok(delete)).await.unwrap();
+        watcher.emit_fs_event(None, Ok(create)).await.unwrap();
+        advance(&clock, Duration::from_millis(200)).await;
+        assert_eq!(changes(&watcher), [("rename".to_string(), Some(old), new)]);
+    }
+
+    #[test]
+    fn watcher_errors_convert_with_their_status_and_code() {
+        let cases = [
+            (
+                BindingWatcherError::InvalidConfig("bad".to_string()),
+                Status::InvalidArg,
+                "INVALID_CONFIG",
+            ),
+            (
+                BindingWatcherError::UnknownEventType("nope".to_string()),
+                Status::InvalidArg,
+                "UNKNOWN_EVENT_TYPE",
+            ),
+            (
+                BindingWatcherError::StartFailed("gone".to_string()),
+                Status::GenericFailure,
+                "START_FAILED",
+            ),
+            (
+                BindingWatcherError::AlreadyRunning,
+                Status::GenericFailure,
+                "ALREADY_RUNNING",
+            ),
+            (
+                BindingWatcherError::RuntimeMissing,
+                Status::GenericFailure,
+                "RUNTIME_MISSING",
+            ),
+        ];
+        for (error, status, code) in cases {
+            let e = napi::Error::from(error);
+            assert_eq!(e.status, status);
+            assert!(e.reason.starts_with(&format!("{code}. ")), "{}", e.reason);
+            assert_eq!(BindingError::from(e).code.as_deref(), Some(code));
+        }
+    }
+
+    #[test]
+    fn error_without_a_code_prefix_has_no_code() {
+        let error = BindingError::from(napi::Error::new(
+            Status::GenericFailure,
+            "Something broke. Try again.",
+        ));
+        assert_eq!(error.kind, "GenericFailure");
+        assert_eq!(error.code, None);
+    }
+
+    #[test]
+    fn change_kind_parses_back_what_it_displays() {
+        for kind in [
+            WatcherChangeKind::Create,
+            WatcherChangeKind::Update,
+            WatcherChangeKind::Delete,
+            WatcherChangeKind::Rename,
+        ] {
+            assert_eq!(kind.to_string().parse::<WatcherChangeKind>().unwrap(), kind);
+            assert_eq!(
+                WatcherChangeKind::try_from(kind.to_string().as_str()).unwrap(),
+                kind
+            );
+        }
+        let error = "moved".parse::<WatcherChangeKind>().unwrap_err();
+        assert_eq!(error.status, Status::InvalidArg);
+        assert_eq!(
+            error.reason,
+            "UNKNOWN_CHANGE_KIND. `moved` is not a change kind."
+        );
+        assert!(WatcherChangeKind::try_from("Create").is_err());
+    }
+
+    #[test]
+    fn ordering_parses_its_two_names_only() {
+        assert_eq!(
+            EventOrdering::from_name("fifo").unwrap(),
+            EventOrdering::Fifo
+        );
+        assert_eq!(
+            EventOrdering::from_name("priority").unwrap(),
+            EventOrdering::Priority
+        );
+        let error = EventOrdering::from_name("lifo").unwrap_err();
+        assert_eq!(error.status, Status::InvalidArg);
+        assert!(
+            error.reason.starts_with("INVALID_CONFIG. "),
+            "{}",
+            error.reason
+        );
+        assert!(error.reason.contains("`lifo`"), "{}", error.reason);
+    }
+
+    #[test]
+    fn change_kind_mask_parses_and_displays_names_joined_by_pipes() {
+        let mask: WatcherChangeKindMask = "create | delete".parse().unwrap();
+        assert_eq!(
+            mask,
+            WatcherChangeKindMask::CREATE | WatcherChangeKindMask::DELETE
+        );
+        assert_eq!(mask.to_string(), "create|delete");
+        assert!("create|moved".parse::<WatcherChangeKindMask>().is_err());
+    }
+
+    #[test]
+    fn change_kind_mask_matches_only_its_kinds() {
+        let mask: WatcherChangeKindMask = "update|rename".parse().unwrap();
+        let matched: Vec<_> = [
+            WatcherChangeKind::Create,
+            WatcherChangeKind::Update,
+            WatcherChangeKind::Delete,
+            WatcherChangeKind::Rename,
+        ]
+        .into_iter()
+        .filter(|&kind| mask.intersects(kind.into()))
+        .collect();
+        assert_eq!(
+            matched,
+            [WatcherChangeKind::Update, WatcherChangeKind::Rename]
+        );
+    }
+
+    #[test]
+    fn equal_events_collapse_in_a_hash_set() {
+        let change = |path: &str| {
+            WatcherEvent::Change(WatcherChangeData {
+                path: path.to_string(),
+                kind: WatcherChangeKind::Update,
+                old_path: None,
+            })
+        };
+        let events: HashSet<_> = [
+            change("/w/a"),
+            change("/w/a"),
+            change("/w/b"),
+            WatcherEvent::Event(BundleEvent::Error(vec![BindingError::new("Error", "boom")])),
+            WatcherEvent::Event(BundleEvent::Error(vec![BindingError::new("Error", "boom")])),
+            WatcherEvent::Close,
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(events.len(), 4);
+        assert!(events.contains(&change("/w/b")));
+    }
+
+    const CREATE: notify::EventKind = notify::EventKind::Create(CreateKind::File);
+    const UPDATE: notify::EventKind = notify::EventKind::Modify(ModifyKind::Any);
+    const DELETE: notify::EventKind = notify::EventKind::Remove(RemoveKind::File);
+
+    /// The changes of `/w/a` emitted once `kinds` happened to it within one debounce window.
+    async fn debounced(kinds: &[notify::EventKind]) -> Vec<(String, Option<String>, String)> {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(
+            &clock,
+            BindingWatcherOptions {
+                debounce_ms: Some(100),
+                ..Default::default()
+            },
+        );
+        for &kind in kinds {
+            let event = fs_event(kind, &["/w/a"]);
+            watcher.emit_fs_event(None, Ok(event)).await.unwrap();
+        }
+        advance(&clock, Duration::from_millis(100)).await;
+        changes(&watcher)
+    }
+
+    #[tokio::test]
+    async fn create_then_delete_within_the_window_emits_nothing() {
+        assert_eq!(debounced(&[CREATE, DELETE]).await, []);
+    }
+
+    #[tokio::test]
+    async fn create_then_update_within_the_window_is_a_create() {
+        assert_eq!(
+            debounced(&[CREATE, UPDATE, UPDATE]).await,
+            [("create".to_string(), None, "/w/a".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_then_create_within_the_window_is_an_update() {
+        assert_eq!(
+            debounced(&[DELETE, CREATE]).await,
+            [("update".to_string(), None, "/w/a".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn history_keeps_only_the_latest_events_up_to_its_capacity() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(
+            &clock,
+            BindingWatcherOptions {
+                history_capacity: Some(4),
+                ..Default::default()
+            },
+        );
+        for _ in 0..6 {
+            let event = WatcherEvent::Event(BundleEvent::BundleStart);
+            watcher.inner.dispatch(None, event).await.unwrap();
+        }
+
+        let seqs = |events: Vec<BindingWatcherEvent>| -> Vec<u32> {
+            events.iter().map(BindingWatcherEvent::seq).collect()
+        };
+        assert_eq!(seqs(watcher.recent_events(None)), [2, 3, 4, 5]);
+        assert_eq!(seqs(watcher.recent_events(Some(2))), [4, 5]);
+    }
+
+    #[tokio::test]
+    async fn debounced_change_is_flushed_once_its_window_passes() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(
+            &clock,
+            BindingWatcherOptions {
+                debounce_ms: Some(100),
+                ..Default::default()
+            },
+        );
+        let update = fs_event(UPDATE, &["/w/a"]);
+        watcher.emit_fs_event(None, Ok(update)).await.unwrap();
+
+        advance(&clock, Duration::from_millis(99)).await;
+        assert_eq!(changes(&watcher), []);
+        advance(&clock, Duration::from_millis(1)).await;
+        assert_eq!(
+            changes(&watcher),
+            [("update".to_string(), None, "/w/a".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn change_made_right_after_start_is_not_missed() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs_watch::normalize(dir.path()).unwrap();
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(
+            &clock,
+            BindingWatcherOptions {
+                paths: vec![dir.to_string_lossy().into_owned()],
+                ..Default::default()
+            },
+        );
+        watcher.start_with(None).unwrap();
+        let file = dir.join("a.txt");
+        std::fs::write(&file, "x").unwrap();
+
+        let file = file.to_string_lossy().into_owned();
+        let seen = tokio::time::timeout(Duration::from_secs(5), async {
+            while !changes(&watcher).iter().any(|(_, _, path)| *path == file) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        watcher.inner.cancel.cancel();
+        assert!(
+            seen.is_ok(),
+            "no change of {file} in {:?}",
+            changes(&watcher)
+        );
+    }
+
+    #[test]
+    fn bundle_event_kind_fails_on_other_events() {
+        let event = BindingWatcherEvent::new(WatcherEvent::Event(BundleEvent::BundleEnd));
+        assert_eq!(event.bundle_event_kind().unwrap(), "BUNDLE_END");
+
+        let event = BindingWatcherEvent::new(WatcherEvent::Close);
+        let e = event.bundle_event_kind().unwrap_err();
+        assert!(
+            e.reason.starts_with("EVENT_KIND_MISMATCH. "),
+            "{}",
+            e.reason
+        );
+        assert_eq!(event.try_bundle_event_kind(), None);
+    }
+
+    #[test]
+    fn watch_change_data_result_fails_on_other_events() {
+        let event = BindingWatcherEvent::new(WatcherEvent::Change(WatcherChangeData {
+            path: "/w/a".to_string(),
+            kind: WatcherChangeKind::Create,
+            old_path: None,
+        }));
+        let data = event.watch_change_data_result().unwrap();
+        assert_eq!(
+            (data.path.as_str(), data.kind()),
+            ("/w/a", WatcherChangeKind::Create)
+        );
+
+        let event = BindingWatcherEvent::new(WatcherEvent::ReStart);
+        let e = event.watch_change_data_result().err().unwrap();
+        assert_eq!(e.status, Status::InvalidArg);
+        assert_eq!(
+            e.reason,
+            "EVENT_KIND_MISMATCH. Expected a `change` event, got `restart`."
+        );
+        assert!(event.try_watch_change_data().is_none());
+    }
+
+    #[test]
+    fn bundle_end_data_result_fails_on_other_events() {
+        let event =
+            BindingWatcherEvent::new(WatcherEvent::Event(BundleEvent::End(BundleEndEventData {
+                output: "dist".to_string(),
+                duration: 12,
+                outputs: Vec::new(),
+            })));
+        let data = event.bundle_end_data_result().unwrap();
+        assert_eq!((data.output.as_str(), data.duration), ("dist", 12));
+
+        // Another bundle event is still the wrong kind.
+        for inner in [
+            WatcherEvent::Event(BundleEvent::BundleEnd),
+            WatcherEvent::Close,
+        ] {
+            let event = BindingWatcherEvent::new(inner);
+            let e = event.bundle_end_data_result().err().unwrap();
+            assert!(
+                e.reason
+                    .starts_with("EVENT_KIND_MISMATCH. Expected a `END` event"),
+                "{}",
+                e.reason
+            );
+            assert!(event.try_bundle_end_data().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn changes_within_the_batch_window_are_one_change_batch() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(
+            &clock,
+            BindingWatcherOptions {
+                batch_window_ms: Some(100),
+                ..Default::default()
+            },
+        );
+        for path in ["/w/a", "/w/b", "/w/c"] {
+            let event = fs_event(UPDATE, &[path]);
+            watcher.emit_fs_event(None, Ok(event)).await.unwrap();
+            advance(&clock, Duration::from_millis(30)).await;
+        }
+        assert!(watcher.recent_events(None).is_empty());
+
+        advance(&clock, Duration::from_millis(10)).await;
+        let events = watcher.recent_events(None);
+        let kinds: Vec<_> = events.iter().map(BindingWatcherEvent::event_kind).collect();
+        assert_eq!(kinds, ["change_batch"]);
+        let paths: Vec<_> = changes(&watcher)
+            .into_iter()
+            .map(|(_, _, path)| path)
+            .collect();
+        assert_eq!(paths, ["/w/a", "/w/b", "/w/c"]);
+    }
+
+    #[tokio::test]
+    async fn restart_numbers_events_from_zero_again() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(&clock, BindingWatcherOptions::default());
+        for _ in 0..3 {
+            let event = WatcherEvent::Event(BundleEvent::BundleStart);
+            watcher.inner.dispatch(None, event).await.unwrap();
+        }
+        watcher.restart().await.unwrap();
+        let event = WatcherEvent::Event(BundleEvent::BundleStart);
+        watcher.inner.dispatch(None, event).await.unwrap();
+
+        let events: Vec<_> = watcher
+            .recent_events(None)
+            .iter()
+            .map(|event| (event.event_kind(), event.seq()))
+            .collect();
+        let event = |kind: &str, seq| (kind.to_string(), seq);
+        assert_eq!(
+            events,
+            [
+                event("event", 0),
+                event("event", 1),
+                event("event", 2),
+                event("restart", 0),
+                event("event", 1),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn events_are_tagged_with_the_config_they_were_emitted_for() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(&clock, BindingWatcherOptions::default());
+        for config in ["client", "server"] {
+            watcher
+                .add_config(config.to_string(), BindingWatcherOptions::default())
+                .unwrap();
+        }
+        for config in ["client", "server", "client"] {
+            let event = WatcherEvent::Event(BundleEvent::BundleStart);
+            watcher.emit(config, event).await.unwrap();
+        }
+        watcher
+            .inner
+            .dispatch(None, WatcherEvent::Close)
+            .await
+            .unwrap();
+
+        let configs: Vec<_> = watcher
+            .recent_events(None)
+            .iter()
+            .map(BindingWatcherEvent::config)
+            .collect();
+        let config = |name: &str| Some(name.to_string());
+        assert_eq!(
+            configs,
+            [config("client"), config("server"), config("client"), None]
+        );
+    }
+
+    #[tokio::test]
+    async fn arg_middlewares_rewrite_events_in_registration_order() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(&clock, BindingWatcherOptions::default());
+        let map_path = |f: fn(&str) -> String| -> ArgMiddleware {
+            Box::new(move |event| match event {
+                WatcherEvent::Change(change) => WatcherEvent::Change(WatcherChangeData {
+                    path: f(&change.path),
+                    ..change
+                }),
+                event => event,
+            })
+        };
+        watcher.add_arg_middleware(map_path(str::to_uppercase));
+        watcher.add_arg_middleware(map_path(|path| format!("{path}.bak")));
+        let change = WatcherChangeData {
+            path: "/w/a".to_string(),
+            kind: WatcherChangeKind::Update,
+            old_path: None,
+        };
+        let event = WatcherEvent::Change(change);
+        watcher.inner.dispatch(None, event).await.unwrap();
+
+        assert_eq!(
+            changes(&watcher),
+            [("update".to_string(), None, "/W/A.bak".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn join_set_tasks_each_become_an_event_then_an_end() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(&clock, BindingWatcherOptions::default());
+        watcher
+            .add_config("app".to_string(), BindingWatcherOptions::default())
+            .unwrap();
+        let mut tasks = JoinSet::new();
+        for task in 0..3 {
+            tasks.spawn(async move {
+                match task {
+                    1 => Err(napi::Error::from_reason("task 1 failed")),
+                    _ => Ok(()),
+                }
+            });
+        }
+        watcher.emit_join_set("app", tasks).await.unwrap();
+
+        let events = watcher.recent_events(None);
+        let kinds: Vec<_> = events
+            .iter()
+            .map(|event| event.bundle_event_kind().unwrap())
+            .collect();
+        let mut finished = kinds[..3].to_vec();
+        finished.sort();
+        assert_eq!(finished, ["BUNDLE_END", "BUNDLE_END", "ERROR"]);
+        assert_eq!(kinds[3..], ["END"]);
+        let errors: Vec<_> = events
+            .iter()
+            .flat_map(|event| match &event.inner {
+                WatcherEvent::Event(BundleEvent::Error(errors)) => errors.clone(),
+                _ => Vec::new(),
+            })
+            .map(|error| error.message)
+            .collect();
+        assert_eq!(errors, ["task 1 failed"]);
+    }
+
+    #[test]
+    fn reconfigured_options_read_back_unchanged() {
+        let watcher = BindingWatcher::with_clock(Arc::new(MockClock::new()));
+        let options = BindingWatcherOptions {
+            paths: vec!["/w".to_string()],
+            ignore: Some(vec!["**/node_modules".to_string()]),
+            debounce_ms: Some(50),
+            ordering: Some("priority".to_string()),
+            ..Default::default()
+        };
+        watcher.reconfigure(options.clone()).unwrap();
+        assert_eq!(format!("{:?}", watcher.options()), format!("{options:?}"));
+    }
+
+    #[test]
+    fn reconfigure_rejects_a_bad_glob_or_ordering_and_keeps_the_options() {
+        let watcher = BindingWatcher::with_clock(Arc::new(MockClock::new()));
+        let invalid = [
+            BindingWatcherOptions {
+                ignore: Some(vec!["a/{b".to_string()]),
+                ..Default::default()
+            },
+            BindingWatcherOptions {
+                ordering: Some("newest".to_string()),
+                ..Default::default()
+            },
+        ];
+        for options in invalid {
+            let e = watcher.reconfigure(options).unwrap_err();
+            assert_eq!(e.status, Status::InvalidArg);
+            assert!(e.reason.starts_with("INVALID_CONFIG. "), "{}", e.reason);
+            assert_eq!(watcher.options().ignore, None);
+            assert_eq!(watcher.options().ordering, None);
+        }
+    }
+
+    #[test]
+    fn errors_read_the_same_list_every_time() {
+        let errors = vec![
+            BindingError::new("Error", "boom"),
+            BindingError::new("TypeError", "not a function"),
+        ];
+        let event =
+            BindingWatcherEvent::new(WatcherEvent::Event(BundleEvent::Error(errors.clone())));
+        let read = || -> Vec<BindingError> {
+            event
+                .errors()
+                .into_iter()
+                .map(|error| match error {
+                    napi::Either::A(_) => panic!("not a BindingError"),
+                    napi::Either::B(error) => error,
+                })
+                .collect()
+        };
+        assert_eq!(read(), errors);
+        assert_eq!(read(), errors);
+        assert!(BindingWatcherEvent::new(WatcherEvent::Close)
+            .errors()
+            .is_empty());
+    }
+
+    #[test]
+    fn equals_compares_kind_and_payload_only() {
+        let change = |path: &str| {
+            WatcherEvent::Change(WatcherChangeData {
+                path: path.to_string(),
+                kind: WatcherChangeKind::Update,
+                old_path: None,
+            })
+        };
+        let event = BindingWatcherEvent::new(change("/w/a"));
+        let elsewhere = BindingWatcherEvent::new(change("/w/a"))
+            .with_config("app")
+            .with_seq(7)
+            .with_cause_seq(Some(3));
+        assert!(event.equals(&elsewhere));
+        assert!(!event.equals(&BindingWatcherEvent::new(change("/w/b"))));
+        assert!(!event.equals(&BindingWatcherEvent::new(WatcherEvent::Close)));
+    }
+
+    #[tokio::test]
+    async fn context_is_attached_to_later_events_and_their_log_records() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(&clock, BindingWatcherOptions::default());
+        let log = tempfile::NamedTempFile::new().unwrap();
+        watcher.set_event_log(EventLog::new(log.reopen().unwrap()));
+        let event = || WatcherEvent::Event(BundleEvent::BundleStart);
+        watcher.inner.dispatch(None, event()).await.unwrap();
+        watcher.set_context("build-1".to_string());
+        watcher.inner.dispatch(None, event()).await.unwrap();
+        watcher.set_context("build-2".to_string());
+        watcher.inner.dispatch(None, event()).await.unwrap();
+
+        let contexts: Vec<_> = watcher
+            .recent_events(None)
+            .iter()
+            .map(BindingWatcherEvent::context)
+            .collect();
+        let context = |name: &str| Some(name.to_string());
+        let expected = [None, context("build-1"), context("build-2")];
+        assert_eq!(contexts, expected);
+        let logged: Vec<_> = std::fs::read_to_string(log.path())
+            .unwrap()
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<event_log::EventRecord>(line)
+                    .unwrap()
+                    .context
+            })
+            .collect();
+        assert_eq!(logged, expected);
+    }
+
+    #[test]
+    fn fallback_listeners_get_only_what_no_subscribed_listener_matched() {
+        let kind_listeners =
+            HashMap::from([(WatcherEventKind::Close, BTreeMap::from([(1, "close")]))]);
+        let change_listeners = BTreeMap::from([(2, (WatcherChangeKindMask::CREATE, "create"))]);
+        let fallback_listeners = BTreeMap::from([(3, "fallback")]);
+        let matched = |event: WatcherEvent, listeners: &[(u32, &'static str)]| -> Vec<_> {
+            match_listeners(
+                &event,
+                listeners,
+                &kind_listeners,
+                &change_listeners,
+                &fallback_listeners,
+            )
+            .into_iter()
+            .map(|(_, listener)| listener)
+            .collect()
+        };
+        let change = |kind| {
+            WatcherEvent::Change(WatcherChangeData {
+                path: "/w/a".to_string(),
+                kind,
+                old_path: None,
+            })
+        };
+
+        assert_eq!(matched(WatcherEvent::Close, &[]), ["close"]);
+        assert_eq!(matched(change(WatcherChangeKind::Create), &[]), ["create"]);
+        assert_eq!(
+            matched(change(WatcherChangeKind::Delete), &[]),
+            ["fallback"]
+        );
+        assert_eq!(matched(WatcherEvent::ReStart, &[]), ["fallback"]);
+        // A catch-all listener isn't subscribed to anything in particular.
+        assert_eq!(
+            matched(WatcherEvent::ReStart, &[(0, "all")]),
+            ["all", "fallback"]
+        );
+        assert_eq!(
+            matched(WatcherEvent::Close, &[(0, "all")]),
+            ["all", "close"]
+        );
+    }
+
+    #[test]
+    fn only_error_events_carry_errors() {
+        let error = BindingError::new("Error", "boom");
+        let event =
+            BindingWatcherEvent::new(WatcherEvent::Event(BundleEvent::Error(vec![error.clone()])));
+        assert_eq!(event.bundle_event_kind().unwrap(), "ERROR");
+        let errors = event.errors();
+        assert!(matches!(&errors[..], [napi::Either::B(e)] if *e == error));
+
+        for event in [
+            WatcherEvent::Event(BundleEvent::BundleStart),
+            WatcherEvent::Event(BundleEvent::BundleEnd),
+            WatcherEvent::ReStart,
+        ] {
+            assert!(BindingWatcherEvent::new(event).errors().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn peak_queue_depth_is_the_highest_backlog_since_start() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(&clock, BindingWatcherOptions::default());
+        let calls: Vec<_> = (0..3).map(|_| watcher.inner.track_call()).collect();
+        drop(calls);
+        let pending = watcher.inner.track_call();
+        assert_eq!(watcher.stats().peak_queue_depth, 3);
+        assert_eq!(watcher.stats().pending_calls, 1);
+
+        watcher.start_with(None).unwrap();
+        assert_eq!(watcher.stats().peak_queue_depth, 1);
+        drop(pending);
+        let _pending = [watcher.inner.track_call(), watcher.inner.track_call()];
+        assert_eq!(watcher.stats().peak_queue_depth, 2);
+        watcher.inner.cancel.cancel();
+    }
+
+    #[tokio::test]
+    async fn change_cancels_the_bundle_in_flight_and_starts_a_fresh_one() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(&clock, BindingWatcherOptions::default());
+        let options = BindingWatcherOptions {
+            cancel_in_flight_on_change: Some(true),
+            ..Default::default()
+        };
+        watcher.add_config("app".to_string(), options).unwrap();
+        let mut tasks = JoinSet::new();
+        tasks.spawn(futures::future::pending::<napi::Result<()>>());
+        let bundling = watcher.clone();
+        let bundle = tokio::spawn(async move { bundling.emit_join_set("app", tasks).await });
+        run_tasks().await;
+
+        let update = fs_event(UPDATE, &["/w/a"]);
+        watcher
+            .emit_fs_event(Some("app"), Ok(update))
+            .await
+            .unwrap();
+        let e = bundle.await.unwrap().unwrap_err();
+        assert_eq!(e.status, Status::Cancelled);
+        assert!(e.reason.starts_with("CANCELLED. "), "{}", e.reason);
+
+        let events: Vec<_> = watcher
+            .recent_events(None)
+            .iter()
+            .map(|event| {
+                event
+                    .try_bundle_event_kind()
+                    .unwrap_or_else(|| event.event_kind())
+            })
+            .collect();
+        assert_eq!(events, ["BUNDLE_START", "change"]);
+    }
+
+    #[tokio::test]
+    async fn summary_on_close_comes_right_before_close() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(
+            &clock,
+            BindingWatcherOptions {
+                summary_on_close: Some(true),
+                ..Default::default()
+            },
+        );
+        let event = WatcherEvent::Event(BundleEvent::BundleStart);
+        watcher.inner.dispatch(None, event).await.unwrap();
+        watcher.close().await.unwrap();
+
+        let events = watcher.recent_events(None);
+        let kinds: Vec<_> = events.iter().map(BindingWatcherEvent::event_kind).collect();
+        assert_eq!(kinds, ["event", "summary", "close"]);
+        assert_eq!(events[1].summary().unwrap().total_events, 1);
+    }
+
+    /// What `poll` hands out under `ordering` once changes of `/w/a` and `/w/b` were emitted
+    /// before an `ERROR` and an `END`: the path of each change and the kind of the other events.
+    async fn polled_in(ordering: &str) -> Vec<String> {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(
+            &clock,
+            BindingWatcherOptions {
+                ordering: Some(ordering.to_string()),
+                ..Default::default()
+            },
+        );
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        assert!(watcher.poll(&mut cx).is_pending());
+        let change = |path: &str| {
+            WatcherEvent::Change(WatcherChangeData {
+                path: path.to_string(),
+                kind: WatcherChangeKind::Update,
+                old_path: None,
+            })
+        };
+        let end = WatcherEvent::Event(BundleEvent::End(BundleEndEventData {
+            output: String::new(),
+            duration: 0,
+            outputs: Vec::new(),
+        }));
+        for event in [change("/w/a"), change("/w/b"), error_event(), end] {
+            watcher.inner.dispatch(None, event).await.unwrap();
+        }
+
+        let mut kinds = Vec::new();
+        while let Poll::Ready(Some(event)) = watcher.poll_event(&mut cx) {
+            kinds.push(match event.try_watch_change_data() {
+                Some(change) => change.path,
+                None => event.bundle_event_kind().unwrap(),
+            });
+        }
+        kinds
+    }
+
+    fn error_event() -> WatcherEvent {
+        WatcherEvent::Event(BundleEvent::Error(vec![BindingError::new("Error", "boom")]))
+    }
+
+    #[tokio::test]
+    async fn priority_ordering_hands_out_errors_and_ends_first() {
+        assert_eq!(
+            polled_in("priority").await,
+            ["ERROR", "END", "/w/a", "/w/b"]
+        );
+        assert_eq!(polled_in("fifo").await, ["/w/a", "/w/b", "ERROR", "END"]);
+    }
+
+    /// Step `watcher` through `poll` until `Ready(None)`, parking the thread while it's pending:
+    /// the smallest executor there is, with nothing of tokio in it.
+    fn poll_to_completion(watcher: &BindingWatcher) -> Vec<WatcherEvent> {
+        struct Unpark(std::thread::Thread);
+        impl std::task::Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut events = Vec::new();
+        loop {
+            match watcher.poll(&mut cx) {
+                Poll::Ready(Some(event)) => events.push(event),
+                Poll::Ready(None) => return events,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn poll_runs_to_ready_none_on_a_hand_rolled_executor() {
+        let clock = Arc::new(MockClock::new());
+        let watcher = watcher(&clock, BindingWatcherOptions::default());
+        // Buffering starts with the first poll, so nothing emitted from here on is missed.
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        assert!(watcher.poll(&mut cx).is_pending());
+        let polled = std::thread::spawn({
+            let watcher = watcher.clone();
+            move || poll_to_completion(&watcher)
+        });
+
+        for event in [WatcherEvent::ReStart, error_event()] {
+            watcher.inner.dispatch(None, event).await.unwrap();
+        }
+        watcher.close().await.unwrap();
+
+        let events = tokio::task::spawn_blocking(move || polled.join().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            events,
+            [WatcherEvent::ReStart, error_event(), WatcherEvent::Close]
+        );
+        assert_eq!(watcher.poll(&mut cx), Poll::Ready(None));
     }
 }
@@ -0,0 +1,100 @@
+//! Per-kind token buckets capping how many events are forwarded to JS per second.
+
+use std::{collections::HashMap, time::Instant};
+
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(per_second: u32, now: Instant) -> Self {
+        Self {
+            capacity: per_second.into(),
+            tokens: per_second.into(),
+            last_refill: now,
+        }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.capacity).min(self.capacity);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Event kinds without a configured limit are never limited.
+#[derive(Default)]
+pub(crate) struct RateLimiter {
+    buckets: HashMap<String, TokenBucket>,
+}
+
+impl RateLimiter {
+    /// Whether an event of `kind` may be forwarded now, given the per-second `limits` by kind.
+    pub(crate) fn allow(
+        &mut self,
+        kind: &str,
+        limits: &HashMap<String, u32>,
+        now: Instant,
+    ) -> bool {
+        let Some(&per_second) = limits.get(kind) else {
+            return true;
+        };
+        let bucket = self
+            .buckets
+            .entry(kind.to_string())
+            .or_insert_with(|| TokenBucket::new(per_second, now));
+        // Pick up a limit changed through `reconfigure`.
+        if bucket.capacity != f64::from(per_second) {
+            *bucket = TokenBucket::new(per_second, now);
+        }
+        bucket.try_take(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::clock::{Clock, MockClock};
+
+    fn allowed(
+        limiter: &mut RateLimiter,
+        limits: &HashMap<String, u32>,
+        clock: &MockClock,
+    ) -> usize {
+        (0..10)
+            .filter(|_| limiter.allow("change", limits, clock.now()))
+            .count()
+    }
+
+    #[test]
+    fn bucket_allows_its_capacity_then_refills_over_time() {
+        let clock = MockClock::new();
+        let limits = HashMap::from([("change".to_string(), 4)]);
+        let mut limiter = RateLimiter::default();
+
+        assert_eq!(allowed(&mut limiter, &limits, &clock), 4);
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(allowed(&mut limiter, &limits, &clock), 2);
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(allowed(&mut limiter, &limits, &clock), 4);
+    }
+
+    #[test]
+    fn kinds_without_a_limit_are_never_limited() {
+        let clock = MockClock::new();
+        let limits = HashMap::from([("close".to_string(), 1)]);
+        let mut limiter = RateLimiter::default();
+
+        assert_eq!(allowed(&mut limiter, &limits, &clock), 10);
+    }
+}
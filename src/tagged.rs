@@ -0,0 +1,137 @@
+//! Conversions for the tagged objects protocol-style callbacks return, e.g. `{ type: "ok", value }`
+//! or `{ type: "err", reason }`.
+//!
+//! Use [`JsTagged`] as `Ret` for such callbacks, e.g. `MaybeAsyncJsCallback<Args, JsTagged<Reply>>`,
+//! then read it with [`JsTagged::get`] into an enum declaring its discriminant field with serde:
+//!
+//! ```ignore
+//! #[derive(Deserialize)]
+//! #[serde(tag = "type", rename_all = "lowercase")]
+//! enum Reply {
+//!     Ok { value: String },
+//!     Err { reason: String },
+//! }
+//! ```
+//!
+//! A discriminant that matches none of the variants fails with `UNKNOWN_DISCRIMINANT`, any other
+//! mismatch between the object and the variant it names with `INVALID_TAGGED_VALUE`.
+
+use std::marker::PhantomData;
+
+use napi::{
+    bindgen_prelude::{FromNapiValue, TypeName, ValidateNapiValue},
+    sys, Status, ValueType,
+};
+use serde::de::DeserializeOwned;
+
+use crate::js_call::pretty_type_name;
+
+/// A JS object to be read into the Rust enum `T` with [`JsTagged::get`].
+///
+/// The object is only deserialized in `get`, for the same reason
+/// [`JsBigInt`](crate::bigint::JsBigInt) checks its range there: a deserialize error while
+/// converting the return value would make the callback's return `Either` fall through to
+/// `UnknownReturnValue`, dropping serde's message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsTagged<T> {
+    value: serde_json::Value,
+    target: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> JsTagged<T> {
+    /// The variant of `T` the object's discriminant names, see the [module docs](self).
+    pub fn get(&self) -> napi::Result<T> {
+        T::deserialize(&self.value).map_err(|e| {
+            // serde has no error kind for this, only the message.
+            let code = if e.to_string().starts_with("unknown variant") {
+                "UNKNOWN_DISCRIMINANT"
+            } else {
+                "INVALID_TAGGED_VALUE"
+            };
+            napi::Error::new(
+                Status::InvalidArg,
+                format!(
+                    "{code}. The object returned isn't a `{}`: {e}.",
+                    pretty_type_name::<T>()
+                ),
+            )
+        })
+    }
+
+    pub fn into_inner(self) -> serde_json::Value {
+        self.value
+    }
+}
+
+impl<T> TypeName for JsTagged<T> {
+    fn type_name() -> &'static str {
+        "Object"
+    }
+
+    fn value_type() -> ValueType {
+        ValueType::Object
+    }
+}
+
+impl<T> ValidateNapiValue for JsTagged<T> {}
+
+impl<T> FromNapiValue for JsTagged<T> {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> napi::Result<Self> {
+        Ok(Self {
+            value: serde_json::Value::from_napi_value(env, napi_val)?,
+            target: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(tag = "type", rename_all = "lowercase")]
+    enum Reply {
+        Ok { value: String },
+        Err { reason: String },
+    }
+
+    fn tagged(value: serde_json::Value) -> JsTagged<Reply> {
+        JsTagged {
+            value,
+            target: PhantomData,
+        }
+    }
+
+    #[test]
+    fn tagged_object_reads_into_the_variant_it_names() {
+        let reply = tagged(json!({ "type": "err", "reason": "nope" })).get();
+        assert_eq!(
+            reply.unwrap(),
+            Reply::Err {
+                reason: "nope".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_discriminant_and_mismatched_fields_have_their_own_codes() {
+        let unknown = tagged(json!({ "type": "maybe" })).get().unwrap_err();
+        assert!(
+            unknown.reason.starts_with("UNKNOWN_DISCRIMINANT. "),
+            "{}",
+            unknown.reason
+        );
+
+        let invalid = tagged(json!({ "type": "ok", "value": 1 }))
+            .get()
+            .unwrap_err();
+        assert!(
+            invalid.reason.starts_with("INVALID_TAGGED_VALUE. "),
+            "{}",
+            invalid.reason
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Forwarding of emitted `WatcherEvent`s out of process, e.g. from a bundler running in a worker
+//! to the main process.
+//!
+//! A [`Transport`] is any sink called with each event as it is emitted. [`ndjson`] and [`stdio`]
+//! serialize the events as NDJSON, and [`NdjsonDecoder`] reads them back on the other end of the
+//! pipe or socket.
+
+use std::{
+    io::{self, BufRead, Write},
+    sync::{Arc, Mutex},
+};
+
+use crate::{is_quiet, WatcherEvent};
+
+/// Called with every emitted event, in emit order. It can't fail the emit, so a sink that can
+/// fail has to report errors on its own.
+pub type Transport = Arc<dyn Fn(&WatcherEvent) + Send + Sync>;
+
+/// Writes each event to `writer` as one JSON object per line, flushing after every event so the
+/// reader sees it right away.
+pub fn ndjson(writer: impl Write + Send + 'static) -> Transport {
+    let writer = Mutex::new(writer);
+    Arc::new(move |event| {
+        let mut writer = writer.lock().unwrap();
+        let written = serde_json::to_writer(&mut *writer, event)
+            .map_err(io::Error::from)
+            .and_then(|()| writer.write_all(b"\n"))
+            .and_then(|()| writer.flush());
+        if let Err(e) = written {
+            if !is_quiet() {
+                tracing::warn!("failed to forward event: {e}");
+            }
+        }
+    })
+}
+
+/// [`ndjson`] over the process's stdout.
+pub fn stdio() -> Transport {
+    ndjson(io::stdout())
+}
+
+/// Reads the events written by [`ndjson`] back from `reader`, one per line. Blank lines are
+/// skipped.
+pub struct NdjsonDecoder<R> {
+    reader: R,
+    line: String,
+}
+
+impl<R: BufRead> NdjsonDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: String::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for NdjsonDecoder<R> {
+    type Item = io::Result<WatcherEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) if self.line.trim().is_empty() => continue,
+                Ok(_) => return Some(serde_json::from_str(&self.line).map_err(io::Error::from)),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BindingError, BundleEvent};
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn decoder_reads_back_what_ndjson_wrote() {
+        let events = [
+            WatcherEvent::Event(BundleEvent::Start),
+            WatcherEvent::Event(BundleEvent::Error(vec![BindingError::new("Error", "boom")])),
+            WatcherEvent::Close,
+        ];
+        let buf = SharedBuf::default();
+        let transport = ndjson(buf.clone());
+        for event in &events {
+            transport(event);
+        }
+
+        let bytes = buf.0.lock().unwrap().clone();
+        let decoded: Vec<_> = NdjsonDecoder::new(bytes.as_slice())
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(decoded, events);
+    }
+
+    #[test]
+    fn decoder_skips_blank_lines_and_reports_malformed_ones() {
+        let input = "\n\"Close\"\n  \nnot json\n";
+        let mut decoder = NdjsonDecoder::new(input.as_bytes());
+
+        assert_eq!(decoder.next().unwrap().unwrap(), WatcherEvent::Close);
+        assert!(decoder.next().unwrap().is_err());
+        assert!(decoder.next().is_none());
+    }
+}
@@ -0,0 +1,468 @@
+//! The pure-Rust part of a `BindingWatcher`: numbering, rate limiting, de-duplication, recording
+//! and forwarding of events, everything short of calling into JS.
+//!
+//! [`WatcherCore::emit_sync`] runs an event through all of that synchronously and records the
+//! result into a `Vec`, so the core logic can be tested without an async runtime. It doesn't
+//! exercise the threadsafe functions the real listeners are called through.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    clock::Clock,
+    event_log::{EventLog, EventRecord},
+    history_capacity, is_quiet,
+    rate_limit::RateLimiter,
+    transport::Transport,
+    ArgMiddleware, BindingError, BindingWatcherEvent, BindingWatcherOptions, BindingWatcherStats,
+    BundleEvent, EventOrdering, PollQueue, WatcherEvent,
+};
+
+pub struct WatcherCore {
+    /// Watcher-wide options. A watch config's own options take precedence over these.
+    pub(crate) options: Mutex<BindingWatcherOptions>,
+    pub(crate) arg_middlewares: Mutex<Vec<Arc<ArgMiddleware>>>,
+    pub(crate) event_log: Mutex<Option<EventLog>>,
+    pub(crate) seq: AtomicU32,
+    pub(crate) cancel: CancellationToken,
+    /// `seq` of the latest change per config, consumed by the next bundle `END`.
+    pub(crate) last_change_seq: Mutex<HashMap<String, u32>>,
+    pub(crate) poll_queue: Mutex<PollQueue>,
+    pub(crate) rate_limiter: Mutex<RateLimiter>,
+    pub(crate) dropped_events: AtomicU32,
+    pub(crate) clock: Arc<dyn Clock>,
+    /// Attached to every event emitted from now on, see `BindingWatcher::set_context`.
+    pub(crate) context: Mutex<Option<String>>,
+    /// The latest emitted events, oldest first, see `history_capacity`.
+    pub(crate) history: Mutex<VecDeque<BindingWatcherEvent>>,
+    pub(crate) transports: Mutex<Vec<Transport>>,
+    /// Distinct errors of the current bundle cycle per config, for `dedupe_errors`.
+    pub(crate) cycle_errors: Mutex<HashMap<String, Vec<BindingError>>>,
+    /// Listener calls started and not settled yet, see [`WatcherCore::track_call`].
+    pub(crate) pending_calls: AtomicU32,
+    /// The highest `pending_calls` seen since the latest `start`.
+    pub(crate) peak_queue_depth: AtomicU32,
+    /// Events that made it past the rate limiter, and the bundle `ERROR`s among them.
+    pub(crate) total_events: AtomicU32,
+    pub(crate) error_events: AtomicU32,
+    /// When the latest `start` happened, or the watcher was created.
+    pub(crate) session_start: Mutex<Instant>,
+}
+
+/// Counts a listener call as pending until dropped, however the call ends.
+pub(crate) struct PendingCall<'a>(&'a AtomicU32);
+
+impl Drop for PendingCall<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl WatcherCore {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            options: Mutex::new(BindingWatcherOptions::default()),
+            arg_middlewares: Mutex::new(Vec::new()),
+            event_log: Mutex::new(None),
+            seq: AtomicU32::new(0),
+            cancel: CancellationToken::new(),
+            last_change_seq: Mutex::new(HashMap::new()),
+            poll_queue: Mutex::new(PollQueue::default()),
+            rate_limiter: Mutex::new(RateLimiter::default()),
+            dropped_events: AtomicU32::new(0),
+            context: Mutex::new(None),
+            history: Mutex::new(VecDeque::new()),
+            transports: Mutex::new(Vec::new()),
+            cycle_errors: Mutex::new(HashMap::new()),
+            pending_calls: AtomicU32::new(0),
+            peak_queue_depth: AtomicU32::new(0),
+            total_events: AtomicU32::new(0),
+            error_events: AtomicU32::new(0),
+            session_start: Mutex::new(clock.now()),
+            clock,
+        }
+    }
+
+    /// `process` `event` and push what the listeners would receive, if anything, onto `sink`.
+    pub fn emit_sync(
+        &self,
+        config: Option<&str>,
+        event: WatcherEvent,
+        sink: &mut Vec<BindingWatcherEvent>,
+    ) {
+        sink.extend(self.process(config, event));
+    }
+
+    /// Count a listener call as pending for as long as the returned guard lives, raising
+    /// `peak_queue_depth` if needed.
+    pub(crate) fn track_call(&self) -> PendingCall<'_> {
+        let depth = self.pending_calls.fetch_add(1, Ordering::AcqRel) + 1;
+        self.peak_queue_depth.fetch_max(depth, Ordering::AcqRel);
+        PendingCall(&self.pending_calls)
+    }
+
+    /// Start a new session on `start`: measure `peak_queue_depth` from the current backlog and
+    /// the session duration from now.
+    pub(crate) fn reset_session(&self) {
+        let depth = self.pending_calls.load(Ordering::Acquire);
+        self.peak_queue_depth.store(depth, Ordering::Release);
+        *self.session_start.lock().unwrap() = self.clock.now();
+    }
+
+    pub(crate) fn stats(&self) -> BindingWatcherStats {
+        let session = self
+            .clock
+            .now()
+            .duration_since(*self.session_start.lock().unwrap());
+        BindingWatcherStats {
+            total_events: self.total_events.load(Ordering::Relaxed),
+            errors: self.error_events.load(Ordering::Relaxed),
+            session_duration_ms: session.as_millis().try_into().unwrap_or(u32::MAX),
+            dropped_events: self.dropped_events.load(Ordering::Relaxed),
+            pending_calls: self.pending_calls.load(Ordering::Relaxed),
+            peak_queue_depth: self.peak_queue_depth.load(Ordering::Relaxed),
+            #[cfg(feature = "metrics")]
+            avg_ffi_latency_ms: crate::ffi_latency::average_ms(),
+            #[cfg(not(feature = "metrics"))]
+            avg_ffi_latency_ms: None,
+        }
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    fn apply_arg_middlewares(&self, event: WatcherEvent) -> WatcherEvent {
+        let middlewares = self.arg_middlewares.lock().unwrap().clone();
+        middlewares
+            .iter()
+            .fold(event, |event, middleware| middleware(event))
+    }
+
+    fn record(&self, config: Option<&str>, seq: u32, context: Option<&str>, event: &WatcherEvent) {
+        if let Some(log) = self.event_log.lock().unwrap().as_mut() {
+            let record = EventRecord {
+                config: config.map(str::to_string),
+                seq,
+                context: context.map(str::to_string),
+                event: event.clone(),
+            };
+            if let Err(e) = log.write_record(&record) {
+                if !is_quiet() {
+                    tracing::warn!("failed to write event log: {e}");
+                }
+            }
+        }
+    }
+
+    /// With `dedupe_errors`, fold the errors of an `ERROR` event into those of the current bundle
+    /// cycle and replace them with the cycle's distinct errors. A `BUNDLE_START` opens a new cycle.
+    fn dedupe_errors(&self, config: Option<&str>, event: WatcherEvent) -> WatcherEvent {
+        if !self.options.lock().unwrap().dedupe_errors.unwrap_or(false) {
+            return event;
+        }
+        let config = config.unwrap_or_default().to_string();
+        let mut cycle_errors = self.cycle_errors.lock().unwrap();
+        match event {
+            WatcherEvent::Event(BundleEvent::BundleStart) => {
+                cycle_errors.remove(&config);
+                event
+            }
+            WatcherEvent::Event(BundleEvent::Error(errors)) => {
+                let seen = cycle_errors.entry(config).or_default();
+                for error in errors {
+                    match seen
+                        .iter_mut()
+                        .find(|e| e.kind == error.kind && e.message == error.message)
+                    {
+                        Some(e) => e.count += error.count,
+                        None => seen.push(error),
+                    }
+                }
+                WatcherEvent::Event(BundleEvent::Error(seen.clone()))
+            }
+            event => event,
+        }
+    }
+
+    /// Number and record `event`, or `None` if it shouldn't reach the listeners at all.
+    pub fn process(
+        &self,
+        config: Option<&str>,
+        event: WatcherEvent,
+    ) -> Option<BindingWatcherEvent> {
+        if self.is_closed() {
+            return None;
+        }
+        if !self.rate_limit_allows(&event) {
+            self.dropped_events.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        let event = self.apply_arg_middlewares(event);
+        let event = self.dedupe_errors(config, event);
+        self.total_events.fetch_add(1, Ordering::Relaxed);
+        if let WatcherEvent::Event(BundleEvent::Error(_)) = event {
+            self.error_events.fetch_add(1, Ordering::Relaxed);
+        }
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let context = self.context.lock().unwrap().clone();
+        self.record(config, seq, context.as_deref(), &event);
+        for transport in self.transports.lock().unwrap().iter() {
+            transport(&event);
+        }
+        let cause_seq = config.and_then(|config| self.track_cause(config, seq, &event));
+
+        let mut binding_event = BindingWatcherEvent::new(event)
+            .with_seq(seq)
+            .with_cause_seq(cause_seq)
+            .with_context(context);
+        if let Some(config) = config {
+            binding_event = binding_event.with_config(config);
+        }
+        let (ordering, capacity) = {
+            let options = self.options.lock().unwrap();
+            (EventOrdering::of(&options), history_capacity(&options))
+        };
+        self.poll_queue
+            .lock()
+            .unwrap()
+            .push(&binding_event, ordering);
+        let mut history = self.history.lock().unwrap();
+        while history.len() >= capacity && history.pop_front().is_some() {}
+        if capacity > 0 {
+            history.push_back(binding_event.clone());
+        }
+        Some(binding_event)
+    }
+
+    fn rate_limit_allows(&self, event: &WatcherEvent) -> bool {
+        let options = self.options.lock().unwrap();
+        let Some(limits) = &options.rate_limits else {
+            return true;
+        };
+        self.rate_limiter
+            .lock()
+            .unwrap()
+            .allow(&event.to_string(), limits, self.clock.now())
+    }
+
+    /// Remember the `seq` of the latest change of `config`, and hand it out as the cause of the
+    /// next bundle `END` of that config.
+    fn track_cause(&self, config: &str, seq: u32, event: &WatcherEvent) -> Option<u32> {
+        let mut last_change_seq = self.last_change_seq.lock().unwrap();
+        match event {
+            WatcherEvent::Change(_) | WatcherEvent::ChangeBatch(_) => {
+                last_change_seq.insert(config.to_string(), seq);
+                None
+            }
+            WatcherEvent::Event(BundleEvent::End(_)) => last_change_seq.remove(config),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn core() -> WatcherCore {
+        WatcherCore::new(Arc::new(MockClock::new()))
+    }
+
+    fn error(message: &str) -> WatcherEvent {
+        WatcherEvent::Event(BundleEvent::Error(vec![BindingError::new(
+            "Error", message,
+        )]))
+    }
+
+    #[test]
+    fn emitted_events_are_numbered_in_order() {
+        let core = core();
+        let mut sink = Vec::new();
+        core.emit_sync(None, WatcherEvent::Event(BundleEvent::Start), &mut sink);
+        core.emit_sync(
+            None,
+            WatcherEvent::Event(BundleEvent::BundleStart),
+            &mut sink,
+        );
+        core.emit_sync(None, WatcherEvent::Close, &mut sink);
+
+        let seqs: Vec<_> = sink.iter().map(|event| event.seq).collect();
+        assert_eq!(seqs, [0, 1, 2]);
+    }
+
+    #[test]
+    fn repeated_errors_of_a_cycle_are_counted_once() {
+        let core = core();
+        core.options.lock().unwrap().dedupe_errors = Some(true);
+        let mut sink = Vec::new();
+        core.emit_sync(Some("a"), error("boom"), &mut sink);
+        core.emit_sync(Some("a"), error("boom"), &mut sink);
+        core.emit_sync(Some("a"), error("other"), &mut sink);
+
+        let WatcherEvent::Event(BundleEvent::Error(errors)) = &sink[2].inner else {
+            panic!("not an error: {}", sink[2].inner);
+        };
+        let counts: Vec<_> = errors
+            .iter()
+            .map(|e| (e.message.as_str(), e.count))
+            .collect();
+        assert_eq!(counts, [("boom", 2), ("other", 1)]);
+
+        core.emit_sync(
+            Some("a"),
+            WatcherEvent::Event(BundleEvent::BundleStart),
+            &mut sink,
+        );
+        core.emit_sync(Some("a"), error("boom"), &mut sink);
+        let WatcherEvent::Event(BundleEvent::Error(errors)) = &sink[4].inner else {
+            panic!("not an error: {}", sink[4].inner);
+        };
+        assert_eq!(errors[0].count, 1);
+    }
+
+    #[test]
+    fn emitted_events_are_kept_in_history() {
+        let core = core();
+        let mut sink = Vec::new();
+        core.emit_sync(
+            Some("a"),
+            WatcherEvent::Event(BundleEvent::Start),
+            &mut sink,
+        );
+        core.emit_sync(Some("a"), WatcherEvent::Close, &mut sink);
+
+        let history: Vec<_> = core
+            .history
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| (event.seq, event.event_kind(), event.config()))
+            .collect();
+        assert_eq!(
+            history,
+            [
+                (0, "event".to_string(), Some("a".to_string())),
+                (1, "close".to_string(), Some("a".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn nothing_is_emitted_once_closed() {
+        let core = core();
+        core.cancel.cancel();
+        let mut sink = Vec::new();
+        core.emit_sync(None, WatcherEvent::Close, &mut sink);
+
+        assert!(sink.is_empty());
+        assert!(core.history.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn events_over_the_rate_limit_are_counted_as_dropped() {
+        let clock = Arc::new(MockClock::new());
+        let core = WatcherCore::new(clock.clone());
+        core.options.lock().unwrap().rate_limits = Some(HashMap::from([("event".to_string(), 2)]));
+        let mut sink = Vec::new();
+        for _ in 0..5 {
+            core.emit_sync(
+                None,
+                WatcherEvent::Event(BundleEvent::BundleStart),
+                &mut sink,
+            );
+        }
+        core.emit_sync(None, WatcherEvent::Close, &mut sink);
+
+        assert_eq!(sink.len(), 3);
+        assert_eq!(core.stats().dropped_events, 3);
+
+        clock.advance(std::time::Duration::from_secs(1));
+        core.emit_sync(
+            None,
+            WatcherEvent::Event(BundleEvent::BundleStart),
+            &mut sink,
+        );
+        assert_eq!(sink.len(), 4);
+    }
+
+    #[test]
+    fn error_seen_three_times_in_a_cycle_has_count_three() {
+        let core = core();
+        core.options.lock().unwrap().dedupe_errors = Some(true);
+        let mut sink = Vec::new();
+        for _ in 0..3 {
+            core.emit_sync(Some("a"), error("boom"), &mut sink);
+        }
+        core.emit_sync(
+            Some("a"),
+            WatcherEvent::Event(BundleEvent::Error(vec![BindingError::new(
+                "TypeError",
+                "boom",
+            )])),
+            &mut sink,
+        );
+
+        let WatcherEvent::Event(BundleEvent::Error(errors)) = &sink[3].inner else {
+            panic!("not an error: {}", sink[3].inner);
+        };
+        let counts: Vec<_> = errors.iter().map(|e| (e.kind.as_str(), e.count)).collect();
+        assert_eq!(counts, [("Error", 3), ("TypeError", 1)]);
+    }
+
+    #[test]
+    fn errors_are_not_deduped_by_default() {
+        let core = core();
+        let mut sink = Vec::new();
+        core.emit_sync(Some("a"), error("boom"), &mut sink);
+        core.emit_sync(Some("a"), error("boom"), &mut sink);
+
+        let WatcherEvent::Event(BundleEvent::Error(errors)) = &sink[1].inner else {
+            panic!("not an error: {}", sink[1].inner);
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].count, 1);
+    }
+
+    #[test]
+    fn end_is_caused_by_the_latest_change_of_its_config() {
+        let core = core();
+        let change = |path: &str| {
+            WatcherEvent::Change(crate::WatcherChangeData {
+                path: path.to_string(),
+                kind: crate::WatcherChangeKind::Update,
+                old_path: None,
+            })
+        };
+        let end = || {
+            WatcherEvent::Event(BundleEvent::End(crate::BundleEndEventData {
+                output: "dist/a.js".to_string(),
+                duration: 1,
+                outputs: Vec::new(),
+            }))
+        };
+        let mut sink = Vec::new();
+        core.emit_sync(Some("a"), change("/w/a"), &mut sink);
+        core.emit_sync(Some("b"), change("/w/b"), &mut sink);
+        core.emit_sync(Some("a"), change("/w/a"), &mut sink);
+        core.emit_sync(Some("a"), end(), &mut sink);
+        core.emit_sync(Some("a"), end(), &mut sink);
+        core.emit_sync(Some("b"), end(), &mut sink);
+
+        let causes: Vec<_> = sink[3..]
+            .iter()
+            .map(BindingWatcherEvent::cause_seq)
+            .collect();
+        assert_eq!(causes, [Some(2), None, Some(1)]);
+        assert!(sink[..3].iter().all(|event| event.cause_seq().is_none()));
+    }
+}
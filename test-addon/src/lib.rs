@@ -0,0 +1,51 @@
+//! `#[napi]` probes exposing pieces of `napi-bind` that the addon itself doesn't export, for the
+//! node tests in `__test__/` to call them with real JS values. Not published.
+
+use std::collections::HashMap;
+
+use napi_bind::{
+    collections::JsMap,
+    js_call::{CallbackReturn, Cloned, MaybeAsyncJsCallback, MaybeAsyncJsCallbackExt},
+};
+use napi_derive::napi;
+
+#[napi]
+#[derive(Clone)]
+pub struct Point {
+    pub x: u32,
+    pub y: u32,
+}
+
+#[napi]
+impl Point {
+    #[napi(constructor)]
+    pub fn new(x: u32, y: u32) -> Self {
+        Self { x, y }
+    }
+}
+
+/// `[x, y]` of the `Point` `callback` returns, directly or through a promise.
+#[napi]
+pub async fn point_returned_by(
+    callback: MaybeAsyncJsCallback<(), Cloned<Point>>,
+) -> napi::Result<Vec<u32>> {
+    let point = callback.await_call(()).await?;
+    Ok(vec![point.x, point.y])
+}
+
+/// How `callback` handed back its value: `promise`, `value` or `unknown`.
+#[napi]
+pub async fn classify_return(callback: MaybeAsyncJsCallback<(), u32>) -> napi::Result<String> {
+    let kind = match callback.call_raw(()).await? {
+        CallbackReturn::Promise(_) => "promise",
+        CallbackReturn::Value(_) => "value",
+        CallbackReturn::Unknown(_) => "unknown",
+    };
+    Ok(kind.to_string())
+}
+
+/// `map` as a plain object.
+#[napi]
+pub fn map_to_object(map: JsMap<String, u32>) -> HashMap<String, u32> {
+    map.into_inner()
+}
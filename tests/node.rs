@@ -0,0 +1,70 @@
+//! Runs the JS specs in `__test__/` under `node --test` against freshly built addons, for what
+//! can only be tested with real JS listeners and values. Skipped when `node` isn't installed.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Build the workspace's addons into their own target dir, so the build doesn't wait on the lock
+/// `cargo test` holds on the default one.
+fn build_addons(target_dir: &Path) {
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--workspace", "--lib", "--target-dir"])
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "building the addons failed");
+}
+
+/// Copy the built `lib{name}` to `dir` under the `.node` extension `require` loads addons by.
+fn addon(target_dir: &Path, name: &str, dir: &Path) -> PathBuf {
+    let file = format!(
+        "{}{name}{}",
+        env::consts::DLL_PREFIX,
+        env::consts::DLL_SUFFIX
+    );
+    let addon = dir.join(name).with_extension("node");
+    fs::copy(target_dir.join("debug").join(file), &addon).unwrap();
+    addon
+}
+
+#[test]
+fn js_specs() {
+    if Command::new("node").arg("--version").output().is_err() {
+        eprintln!("skipping the JS specs: `node` is not installed");
+        return;
+    }
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = root.join("target").join("node-tests");
+    build_addons(&target_dir);
+    let dir = tempfile::tempdir().unwrap();
+    let mut specs: Vec<_> = fs::read_dir(root.join("__test__"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(".spec.mjs"))
+        .collect();
+    specs.sort();
+
+    let output = Command::new("node")
+        .arg("--test")
+        .args(&specs)
+        .env("NAPI_BIND_QUIET", "1")
+        .env(
+            "NAPI_BIND_ADDON",
+            addon(&target_dir, "napi_bind", dir.path()),
+        )
+        .env(
+            "NAPI_BIND_TEST_ADDON",
+            addon(&target_dir, "napi_bind_test_addon", dir.path()),
+        )
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,9 @@
+use napi_bind::js_call::JsCallbackReturn;
+
+struct Unconvertible;
+
+fn returns<Ret: JsCallbackReturn>() {}
+
+fn main() {
+    returns::<Unconvertible>();
+}
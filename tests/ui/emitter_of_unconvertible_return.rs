@@ -0,0 +1,10 @@
+// A struct position checks its bounds before `JsCallbackReturn` gets a say, so rustc names the
+// missing supertraits here. They should still be reported against `Emitter`'s `Ret: JsCallbackReturn`
+// bound rather than against the `Either` NAPI-RS converts callback returns through.
+use napi_bind::emitter::Emitter;
+
+struct Unconvertible;
+
+fn main() {
+    let _ = Emitter::<(), Unconvertible>::new();
+}